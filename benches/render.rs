@@ -0,0 +1,116 @@
+//! Times rendering 100 widgets that all need a full redraw every frame,
+//! on the main thread and on the compute task pool.
+//!
+//! ```shell
+//! $ cargo bench --bench render --features multi_threaded
+//! ```
+
+use std::time::{Duration, Instant};
+
+use bevy::{input::InputPlugin, prelude::*, window::Ime};
+use bevy_cosmic_edit::{
+    cosmic_text::{Attrs, Family, Metrics},
+    prelude::*,
+    CosmicBackgroundColor, RenderThreads,
+};
+
+const WIDGETS: usize = 100;
+const WARMUP_FRAMES: usize = 5;
+const FRAMES: usize = 50;
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog.
+Sphinx of black quartz, judge my vow!
+0123456789 (){}[] => -> != <= >=
+Pack my box with five dozen liquor jugs.";
+
+fn setup(mut commands: Commands, mut font_system: ResMut<CosmicFontSystem>) {
+    let attrs = Attrs::new().family(Family::Name("Victor Mono"));
+    for i in 0..WIDGETS {
+        commands.spawn((
+            TextEdit2d,
+            CosmicEditBuffer::new(&mut font_system, Metrics::new(16., 20.)).with_text(
+                &mut font_system,
+                &format!("Widget {i}\n{TEXT}"),
+                attrs.clone(),
+            ),
+            Sprite {
+                custom_size: Some(Vec2::new(400., 120.)),
+                ..default()
+            },
+        ));
+    }
+}
+
+/// Changing the background redraws every widget entirely
+fn change_background(mut widgets: Query<&mut CosmicBackgroundColor>, mut frame: Local<u8>) {
+    *frame = frame.wrapping_add(1);
+    for mut background in widgets.iter_mut() {
+        background.0 = Color::srgb_u8(255, 255, *frame);
+    }
+}
+
+fn app(threads: RenderThreads) -> App {
+    let font_config = CosmicFontConfig {
+        fonts_dir_path: None,
+        font_bytes: Some(vec![include_bytes!(
+            "../assets/fonts/VictorMono-Regular.ttf"
+        )]),
+        load_system_fonts: false,
+    };
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), InputPlugin))
+        .init_asset::<Image>()
+        .init_asset::<TextureAtlasLayout>()
+        // usually added by the window plugin
+        .add_message::<Ime>()
+        .add_plugins(CosmicEditPlugin { font_config })
+        .insert_resource(threads)
+        .add_systems(Startup, setup)
+        .add_systems(Update, change_background);
+    app
+}
+
+/// Average frame time, and the pixels of every widget after the last frame
+fn run(threads: RenderThreads) -> (Duration, Vec<Vec<u8>>) {
+    let mut app = app(threads);
+    for _ in 0..WARMUP_FRAMES {
+        app.update();
+    }
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        app.update();
+    }
+    let frame_time = start.elapsed() / FRAMES as u32;
+
+    let world = app.world_mut();
+    let images = world
+        .query::<&Sprite>()
+        .iter(world)
+        .map(|sprite| sprite.image.clone())
+        .collect::<Vec<_>>();
+    let assets = world.resource::<Assets<Image>>();
+    let pixels = images
+        .iter()
+        .map(|image| assets.get(image).unwrap().data.clone().unwrap())
+        .collect();
+    (frame_time, pixels)
+}
+
+fn main() {
+    let (main_thread, main_thread_pixels) = run(RenderThreads::MainThread);
+    let (task_pool, task_pool_pixels) = run(RenderThreads::TaskPool);
+    assert!(
+        main_thread_pixels == task_pool_pixels,
+        "Rendering on the task pool should look exactly the same"
+    );
+
+    println!("{WIDGETS} widgets, average of {FRAMES} frames");
+    println!("main thread: {main_thread:?} per frame");
+    println!("task pool:   {task_pool:?} per frame");
+    println!(
+        "speed-up:    {:.2}x on {} threads",
+        main_thread.as_secs_f64() / task_pool.as_secs_f64(),
+        bevy::tasks::ComputeTaskPool::get().thread_num()
+    );
+}
@@ -56,6 +56,6 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .add_plugins(CosmicEditPlugin { font_config })
         .add_systems(Startup, setup)
-        .add_systems(Update, (print_editor_text, deselect_editor_on_esc))
+        .add_systems(Update, print_editor_text)
         .run();
 }
@@ -2,9 +2,9 @@ use bevy::{prelude::*, window::{CursorIcon, SystemCursorIcon}};
 use bevy_cosmic_edit::{
     cosmic_text::{Attrs, AttrsOwned, Metrics},
     prelude::*,
-    CosmicBackgroundColor, CosmicBackgroundImage, CosmicTextAlign, CosmicWrap, CursorColor,
-    DefaultAttrs, HorizontalAlign, HoverCursor, MaxChars, MaxLines, SelectedTextColor,
-    SelectionColor, VerticalAlign,
+    CosmicBackgroundColor, CosmicBackgroundImage, CosmicPadding, CosmicTextAlign, CosmicWrap,
+    CursorColor, DefaultAttrs, HorizontalAlign, HoverCursor, MaxChars, MaxLines,
+    SelectedTextColor, SelectionColor, VerticalAlign,
 };
 
 #[derive(Resource)]
@@ -31,6 +31,8 @@ fn setup(mut commands: Commands, mut font_system: ResMut<CosmicFontSystem>) {
                 vertical: VerticalAlign::Center,
             },
             CosmicBackgroundImage(None),
+            // inside the node's border
+            CosmicPadding::axes(6., 0.).with_node(),
             DefaultAttrs(AttrsOwned::new(&attrs)),
             MaxChars(15),
             MaxLines(1),
@@ -93,6 +95,5 @@ fn main() {
         .add_plugins(CosmicEditPlugin::default())
         .add_systems(Startup, setup)
         .add_systems(Update, text_swapper)
-        .add_systems(Update, deselect_editor_on_esc)
         .run();
 }
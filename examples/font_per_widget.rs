@@ -205,6 +205,5 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .add_plugins(CosmicEditPlugin { ..default() })
         .add_systems(Startup, setup)
-        .add_systems(Update, deselect_editor_on_esc)
         .run();
 }
@@ -35,11 +35,8 @@ fn main() {
         .add_systems(Startup, setup)
         .add_systems(
             Update,
-            (
-                deselect_editor_on_esc,
-                // If you don't .after(InputSet) you'll just see the hashed-out safe text
-                print_editor_text.after(bevy_cosmic_edit::input::InputSet),
-            ),
+            // If you don't .after(InputSet) you'll just see the hashed-out safe text
+            print_editor_text.after(bevy_cosmic_edit::input::InputSet),
         )
         .run();
 }
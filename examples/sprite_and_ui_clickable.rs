@@ -1,8 +1,8 @@
 use bevy::prelude::*;
 use bevy_cosmic_edit::{
+    change::CosmicTextEdited,
     cosmic_text::{Attrs, AttrsOwned},
     input::hover::{TextHoverIn, TextHoverOut},
-    input::CosmicTextChanged,
     prelude::*,
     CosmicTextAlign, MaxLines,
 };
@@ -53,7 +53,7 @@ fn setup(mut commands: Commands) {
 fn ev_test(
     mut evr_on: MessageReader<TextHoverIn>,
     mut evr_out: MessageReader<TextHoverOut>,
-    mut evr_type: MessageReader<CosmicTextChanged>,
+    mut evr_type: MessageReader<CosmicTextEdited>,
 ) {
     for _ev in evr_on.read() {
         println!("IN");
@@ -70,8 +70,9 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(CosmicEditPlugin { ..default() })
+        // clicking the background unfocuses too
+        .insert_resource(FocusPolicy::BLUR)
         .add_systems(Startup, setup)
-        .add_systems(Update, deselect_editor_on_esc)
         .add_systems(Update, ev_test)
         .run();
 }
@@ -0,0 +1,326 @@
+//! Widgets that grow and shrink with their text, see [`AutoSize`].
+//!
+//! Whenever its text, [`CosmicWrap`], [`CosmicPadding`] or [`AutoSize`] changes, the text
+//! of a widget is measured, and the size it needs, including its padding, is written to
+//! the [`Sprite::custom_size`] of a [`TextEdit2d`] or the `width` and `height` of a
+//! [`TextEdit`]'s [`Node`].
+//! [`CosmicWrap::Wrap`] wraps text at `max_width`.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::auto_size::{AutoSize, WidgetResized};
+//!
+//! fn setup(mut commands: Commands) {
+//!     // a chat input that grows with its text, up to 100 pixels high
+//!     commands
+//!         .spawn((
+//!             TextEdit,
+//!             CosmicEditBuffer::default(),
+//!             AutoSize::fixed_width(300.).with_height(20., 100.),
+//!         ))
+//!         .observe(|resized: On<WidgetResized>| {
+//!             info!("Input is now {} high", resized.size.y);
+//!         });
+//! }
+//! ```
+
+use crate::{
+    change::TextChanges,
+    password::PasswordSet,
+    prelude::*,
+    render::RenderSet,
+    render_implementations::{padding_insets, WidgetScaleFactor},
+    BufferMutExtras, CosmicPadding,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        auto_size
+            .after(PasswordSet)
+            .before(RenderSet)
+            .before(bevy::ui::UiSystems::Layout),
+    )
+    .add_message::<WidgetResized>()
+    .register_type::<AutoSize>();
+}
+
+/// Sizes a widget to fit its text, in logical pixels. See the [module docs](self)
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct AutoSize {
+    pub min_width: f32,
+    pub max_width: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+}
+
+impl Default for AutoSize {
+    fn default() -> Self {
+        Self {
+            min_width: 0.,
+            max_width: f32::INFINITY,
+            min_height: 0.,
+            max_height: f32::INFINITY,
+        }
+    }
+}
+
+impl AutoSize {
+    /// Only grows in height, wrapping text at `width`
+    pub fn fixed_width(width: f32) -> Self {
+        Self::default().with_width(width, width)
+    }
+
+    /// Only grows in width
+    pub fn fixed_height(height: f32) -> Self {
+        Self::default().with_height(height, height)
+    }
+
+    pub fn with_width(mut self, min: f32, max: f32) -> Self {
+        self.min_width = min;
+        self.max_width = max;
+        self
+    }
+
+    pub fn with_height(mut self, min: f32, max: f32) -> Self {
+        self.min_height = min;
+        self.max_height = max;
+        self
+    }
+
+    fn clamp(&self, size: Vec2) -> Vec2 {
+        Vec2::new(
+            size.x.max(self.min_width).min(self.max_width),
+            size.y.max(self.min_height).min(self.max_height),
+        )
+    }
+}
+
+/// Sent when [`AutoSize`] changes the size of a widget.
+///
+/// Both triggered on the widget, for observers, and written as a message
+#[derive(Message, EntityEvent, Reflect, Debug, Clone, Copy)]
+pub struct WidgetResized {
+    pub entity: Entity,
+    /// The new size in logical pixels
+    pub size: Vec2,
+}
+
+fn auto_size(
+    mut widgets: ParamSet<(
+        TextChanges,
+        Query<(
+            Entity,
+            &AutoSize,
+            EditorBuffer,
+            &CosmicWrap,
+            &WidgetScaleFactor,
+            Option<&CosmicPadding>,
+            Option<&ComputedNode>,
+            Option<&mut Sprite>,
+            Option<&mut Node>,
+            Has<TextEdit>,
+        )>,
+    )>,
+    // only measure again when the text or something else that affects the size changed
+    changed: Query<
+        (),
+        Or<(
+            Changed<AutoSize>,
+            Changed<CosmicWrap>,
+            Changed<CosmicPadding>,
+            Changed<WidgetScaleFactor>,
+        )>,
+    >,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut resized: MessageWriter<WidgetResized>,
+    mut commands: Commands,
+) {
+    let text_changes = widgets.p0().read();
+    for (entity, auto_size, mut editor, wrap, scale, padding, ui_node, sprite, node, is_ui) in
+        widgets.p1().iter_mut()
+    {
+        if !text_changes.contains(&entity) && !changed.contains(entity) {
+            continue;
+        }
+        // the buffer is laid out in physical pixels
+        let scale = scale.0;
+        let (min, max) = padding_insets(padding, ui_node);
+        let padding = min + max;
+        let wrap_width = match wrap {
+            CosmicWrap::Wrap if auto_size.max_width.is_finite() => {
+                Some((auto_size.max_width - padding.x).max(0.) * scale)
+            }
+            _ => None,
+        };
+        // measuring doesn't change what the widget looks like, so keep its size and redraw flag
+        let (old_width, old_height) = editor.size();
+        let redraw = editor.redraw();
+        editor.set_size(&mut font_system.0, wrap_width, None);
+        let text_size = editor.borrow_with(&mut font_system.0).expected_size() / scale;
+        editor.set_size(&mut font_system.0, old_width, old_height);
+        editor.set_redraw(redraw);
+        let size = auto_size.clamp((text_size + padding).ceil());
+
+        let changed = match (is_ui, sprite, node) {
+            (true, _, Some(mut node)) => {
+                let (width, height) = (Val::Px(size.x), Val::Px(size.y));
+                let changed = node.width != width || node.height != height;
+                if changed {
+                    node.width = width;
+                    node.height = height;
+                }
+                changed
+            }
+            (false, Some(mut sprite), _) => {
+                let changed = sprite.custom_size != Some(size);
+                if changed {
+                    sprite.custom_size = Some(size);
+                }
+                changed
+            }
+            _ => false,
+        };
+        if changed {
+            let event = WidgetResized { entity, size };
+            resized.write(event);
+            commands.trigger(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::Metrics;
+
+    use super::*;
+    use crate::change::{CosmicTextEdited, EditKind};
+    use crate::history::EditOrigin;
+
+    #[test]
+    fn grows_with_lines() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_message::<CosmicTextEdited>()
+            .add_plugins(plugin);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.)).with_text(
+            &mut font_system,
+            "one",
+            cosmic_text::Attrs::new(),
+        );
+        app.insert_resource(CosmicFontSystem(font_system));
+        let widget = app
+            .world_mut()
+            .spawn((
+                TextEdit2d,
+                buffer,
+                CosmicPadding::axes(0., 5.),
+                AutoSize::fixed_width(100.).with_height(0., 50.),
+            ))
+            .id();
+        let size = |app: &App| {
+            app.world()
+                .get::<Sprite>(widget)
+                .unwrap()
+                .custom_size
+                .unwrap()
+        };
+
+        app.update();
+        assert_eq!(size(&app), Vec2::new(100., 30.));
+
+        app.world_mut()
+            .resource_scope(|world, mut font_system: Mut<CosmicFontSystem>| {
+                world.get_mut::<CosmicEditBuffer>(widget).unwrap().set_text(
+                    &mut font_system,
+                    "one\ntwo\nthree",
+                    cosmic_text::Attrs::new(),
+                );
+            });
+        app.update();
+        assert_eq!(size(&app), Vec2::new(100., 50.));
+        assert_eq!(app.world().resource::<Messages<WidgetResized>>().len(), 2);
+
+        // nothing changed, so the text isn't measured again
+        app.world_mut()
+            .get_mut::<Sprite>(widget)
+            .unwrap()
+            .custom_size = Some(Vec2::ONE);
+        app.update();
+        assert_eq!(size(&app), Vec2::ONE);
+
+        app.world_mut()
+            .get_mut::<AutoSize>(widget)
+            .unwrap()
+            .max_height = 40.;
+        app.update();
+        assert_eq!(size(&app), Vec2::new(100., 40.));
+    }
+
+    #[test]
+    fn idle_focused_widget_is_not_measured() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_message::<CosmicTextEdited>()
+            .add_plugins(plugin);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.)).with_text(
+            &mut font_system,
+            "one",
+            cosmic_text::Attrs::new(),
+        );
+        let editor = CosmicEditor::clone_from_buffer(&buffer);
+        app.insert_resource(CosmicFontSystem(font_system));
+        let widget = app
+            .world_mut()
+            .spawn((
+                TextEdit2d,
+                buffer,
+                editor,
+                AutoSize::fixed_width(100.).with_height(0., 50.),
+            ))
+            .id();
+        let size = |app: &App| {
+            app.world()
+                .get::<Sprite>(widget)
+                .unwrap()
+                .custom_size
+                .unwrap()
+        };
+
+        app.update();
+        assert_eq!(size(&app), Vec2::new(100., 20.));
+
+        // the cursor blinking changes the editor every frame
+        app.world_mut()
+            .get_mut::<Sprite>(widget)
+            .unwrap()
+            .custom_size = Some(Vec2::ONE);
+        for _ in 0..3 {
+            app.world_mut()
+                .get_mut::<CosmicEditor>(widget)
+                .unwrap()
+                .set_redraw(true);
+            app.update();
+        }
+        assert_eq!(size(&app), Vec2::ONE);
+
+        app.world_mut().write_message(CosmicTextEdited {
+            entity: widget,
+            kind: EditKind::Insert,
+            origin: EditOrigin::Typing,
+            start: cosmic_text::Cursor::new(0, 3),
+            end: cosmic_text::Cursor::new(0, 4),
+            inserted: "!".into(),
+            removed: String::new(),
+            text: None,
+        });
+        app.update();
+        assert_eq!(size(&app), Vec2::new(100., 20.));
+    }
+}
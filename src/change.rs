@@ -0,0 +1,291 @@
+//! Reporting what changed in a widget's text.
+//!
+//! Every edit, whether typed, pasted, undone or made with an
+//! [`EditCommand`](crate::command::EditCommand), sends a [`CosmicTextEdited`]. It is both
+//! written as a message and triggered on the widget, so it can be observed:
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::change::{CosmicTextEdited, ReportFullText};
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands
+//!         .spawn((TextEdit, CosmicEditBuffer::default(), ReportFullText))
+//!         .observe(|edited: On<CosmicTextEdited>| {
+//!             info!("{:?} {:?}, text is now {:?}", edited.origin, edited.inserted, edited.text);
+//!         });
+//! }
+//! ```
+//!
+//! Copying the whole text on every keystroke gets slow for large buffers, so it is only
+//! included for widgets with [`ReportFullText`]. The deprecated
+//! [`CosmicTextChanged`] is only sent for those widgets too.
+
+use bevy::ecs::{entity::EntityHashSet, system::SystemParam};
+use cosmic_text::{Change, Cursor};
+
+#[allow(deprecated)]
+use crate::input::CosmicTextChanged;
+use crate::{
+    history::{EditHistory, EditOrigin},
+    placeholder::Placeholder,
+    prelude::*,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_message::<CosmicTextEdited>()
+        .register_type::<EditKind>()
+        .register_type::<ReportFullText>();
+}
+
+/// Include the full text in [`CosmicTextEdited`], and send the deprecated [`CosmicTextChanged`]
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct ReportFullText;
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    Insert,
+    Delete,
+    /// Text was removed and other text inserted, e.g. typing over a selection
+    Replace,
+}
+
+/// Sent when the text of a widget changes, see the [module docs](self)
+#[derive(Message, EntityEvent, Debug, Clone)]
+pub struct CosmicTextEdited {
+    pub entity: Entity,
+    pub kind: EditKind,
+    pub origin: EditOrigin,
+    /// Start of the edit
+    pub start: Cursor,
+    /// End of the inserted text after the edit, or of the removed text before it
+    /// for [`EditKind::Delete`]
+    pub end: Cursor,
+    pub inserted: String,
+    pub removed: String,
+    /// The whole text after the edit, only for widgets with [`ReportFullText`]
+    pub text: Option<String>,
+}
+
+impl CosmicTextEdited {
+    /// Summarises a [`Change`], `None` if it didn't change anything
+    pub(crate) fn from_change(entity: Entity, change: &Change, origin: EditOrigin) -> Option<Self> {
+        let start = change
+            .items
+            .iter()
+            .map(|item| item.start)
+            .min_by_key(|cursor| (cursor.line, cursor.index))?;
+
+        let mut inserted = String::new();
+        let mut removed = String::new();
+        let mut removed_start = None;
+        for item in &change.items {
+            if item.insert {
+                inserted.push_str(&item.text);
+            } else if removed_start.is_some_and(|start: Cursor| {
+                (start.line, start.index) == (item.end.line, item.end.index)
+            }) {
+                // backspacing removes text in front of what was already removed
+                removed.insert_str(0, &item.text);
+                removed_start = Some(item.start);
+            } else {
+                removed.push_str(&item.text);
+                removed_start.get_or_insert(item.start);
+            }
+        }
+
+        let (kind, end) = match (inserted.is_empty(), removed.is_empty()) {
+            (true, true) => return None,
+            (false, true) => (EditKind::Insert, advance(start, &inserted)),
+            (true, false) => (EditKind::Delete, advance(start, &removed)),
+            (false, false) => (EditKind::Replace, advance(start, &inserted)),
+        };
+
+        Some(Self {
+            entity,
+            kind,
+            origin,
+            start,
+            end,
+            inserted,
+            removed,
+            text: None,
+        })
+    }
+}
+
+/// The position after `text` if it was placed at `start`
+fn advance(start: Cursor, text: &str) -> Cursor {
+    match text.rsplit_once('\n') {
+        Some((before, last)) => {
+            Cursor::new(start.line + before.matches('\n').count() + 1, last.len())
+        }
+        None => Cursor::new(start.line, start.index + text.len()),
+    }
+}
+
+#[allow(deprecated)]
+type TextChangedWriter<'w> = MessageWriter<'w, CosmicTextChanged>;
+
+/// Sends [`CosmicTextEdited`], and the deprecated [`CosmicTextChanged`] for widgets with
+/// [`ReportFullText`]
+#[derive(SystemParam)]
+pub(crate) struct TextEditWriter<'w, 's> {
+    edited: MessageWriter<'w, CosmicTextEdited>,
+    changed: TextChangedWriter<'w>,
+    report_full_text: Query<'w, 's, (), With<ReportFullText>>,
+    commands: Commands<'w, 's>,
+}
+
+impl TextEditWriter<'_, '_> {
+    /// Reports the last edit recorded by `history`, if there is one.
+    ///
+    /// `text` is only called for widgets with [`ReportFullText`]
+    #[allow(deprecated)]
+    pub fn write(
+        &mut self,
+        entity: Entity,
+        history: &mut EditHistory,
+        text: impl FnOnce() -> String,
+    ) {
+        let Some((change, origin)) = history.take_last_edit() else {
+            return;
+        };
+        let Some(mut edited) = CosmicTextEdited::from_change(entity, &change, origin) else {
+            return;
+        };
+
+        if self.report_full_text.contains(entity) {
+            let text = text();
+            edited.text = Some(text.clone());
+            self.changed.write(CosmicTextChanged((entity, text)));
+        }
+        self.edited.write(edited.clone());
+        self.commands.trigger(edited);
+    }
+}
+
+/// Widgets whose text changed since the system using this last ran.
+///
+/// Edits to a focused widget are found through [`CosmicTextEdited`], since a
+/// [`CosmicEditor`] is also changed by things like the cursor blinking
+#[derive(SystemParam)]
+pub(crate) struct TextChanges<'w, 's> {
+    edited: MessageReader<'w, 's, CosmicTextEdited>,
+    changed: Query<
+        'w,
+        's,
+        Entity,
+        Or<(
+            Changed<CosmicEditBuffer>,
+            Added<CosmicEditor>,
+            Changed<Placeholder>,
+        )>,
+    >,
+}
+
+impl TextChanges<'_, '_> {
+    pub fn read(&mut self) -> EntityHashSet {
+        let mut changed: EntityHashSet = self.changed.iter().collect();
+        changed.extend(self.edited.read().map(|edited| edited.entity));
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use cosmic_text::{ChangeItem, Edit, Editor, FontSystem, Metrics};
+
+    use super::*;
+
+    fn item(start: Cursor, end: Cursor, text: &str, insert: bool) -> ChangeItem {
+        ChangeItem {
+            start,
+            end,
+            text: text.into(),
+            insert,
+        }
+    }
+
+    #[test]
+    fn summarises_changes() {
+        // typing "ab\nc" over a selected "xyz"
+        let change = Change {
+            items: vec![
+                item(Cursor::new(0, 2), Cursor::new(0, 5), "xyz", false),
+                item(Cursor::new(0, 2), Cursor::new(0, 4), "ab", true),
+                item(Cursor::new(0, 4), Cursor::new(1, 1), "\nc", true),
+            ],
+        };
+        let edited =
+            CosmicTextEdited::from_change(Entity::PLACEHOLDER, &change, EditOrigin::Typing)
+                .unwrap();
+        assert_eq!(edited.kind, EditKind::Replace);
+        assert_eq!((edited.start.line, edited.start.index), (0, 2));
+        assert_eq!((edited.end.line, edited.end.index), (1, 1));
+        assert_eq!(edited.inserted, "ab\nc");
+        assert_eq!(edited.removed, "xyz");
+
+        // two backspaces
+        let change = Change {
+            items: vec![
+                item(Cursor::new(0, 4), Cursor::new(0, 5), "o", false),
+                item(Cursor::new(0, 3), Cursor::new(0, 4), "l", false),
+            ],
+        };
+        let edited =
+            CosmicTextEdited::from_change(Entity::PLACEHOLDER, &change, EditOrigin::Deleting)
+                .unwrap();
+        assert_eq!(edited.kind, EditKind::Delete);
+        assert_eq!((edited.start.index, edited.end.index), (3, 5));
+        assert_eq!(edited.removed, "lo");
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn text_is_only_built_with_report_full_text() {
+        let mut app = App::new();
+        app.add_message::<CosmicTextEdited>()
+            .add_message::<CosmicTextChanged>();
+        let mut font_system = FontSystem::new_with_fonts([]);
+        let mut editor = Editor::new(Buffer::new(&mut font_system, Metrics::new(20., 20.)));
+
+        for report_full_text in [false, true] {
+            let mut history = EditHistory::default();
+            history.begin(&mut editor);
+            editor.insert_string("a", None);
+            history.commit(&mut editor, EditOrigin::Typing);
+            let entity = app.world_mut().spawn(history).id();
+            if report_full_text {
+                app.world_mut().entity_mut(entity).insert(ReportFullText);
+            }
+
+            let built = app
+                .world_mut()
+                .run_system_once(
+                    move |mut edits: TextEditWriter, mut histories: Query<&mut EditHistory>| {
+                        let mut built = 0;
+                        let mut history = histories.get_mut(entity).unwrap();
+                        edits.write(entity, &mut history, || {
+                            built += 1;
+                            "a".to_string()
+                        });
+                        built
+                    },
+                )
+                .unwrap();
+            assert_eq!(built, report_full_text as usize);
+
+            let changed = app
+                .world()
+                .resource::<Messages<CosmicTextChanged>>()
+                .iter_current_update_messages()
+                .filter(|CosmicTextChanged((changed, _))| *changed == entity)
+                .count();
+            assert_eq!(changed, report_full_text as usize);
+        }
+    }
+}
@@ -0,0 +1,447 @@
+//! Editing widgets from code.
+//!
+//! Trigger a [`CosmicEditCommand`] on a widget to edit its text, cursor or selection.
+//! Commands behave the same whether or not the widget is focused, go through its
+//! [`EditHistory`] and send [`CosmicTextEdited`](crate::change::CosmicTextEdited)
+//! like keyboard input does.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! fn greet(mut commands: Commands, widget: Single<Entity, With<CosmicEditBuffer>>) {
+//!     commands.trigger(CosmicEditCommand::new(*widget, EditCommand::SelectAll));
+//!     commands.trigger(CosmicEditCommand::new(
+//!         *widget,
+//!         EditCommand::InsertText("Hello!".into()),
+//!     ));
+//! }
+//! ```
+
+use cosmic_text::{Action, Cursor, Edit, Motion, Selection};
+
+use crate::{
+    buffer::clamp_cursor,
+    change::TextEditWriter,
+    filter::InputRejected,
+    history::{EditHistory, EditOrigin},
+    input::{
+        clipboard,
+        keyboard::{delete_limited, insert_text_limited, InsertLimits},
+    },
+    prelude::*,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_observer(apply_edit_command);
+}
+
+/// An edit to apply to a widget, see [`CosmicEditCommand`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditCommand {
+    /// Inserts text at the cursor, replacing the selection
+    InsertText(String),
+    /// Replaces the text between `start` and `end`
+    ReplaceRange {
+        start: Cursor,
+        end: Cursor,
+        text: String,
+    },
+    /// Deletes the text between `start` and `end`
+    DeleteRange {
+        start: Cursor,
+        end: Cursor,
+    },
+    /// Selects from `anchor` to `cursor`, and moves the cursor there
+    SetSelection {
+        anchor: Cursor,
+        cursor: Cursor,
+    },
+    /// Removes the selection without moving the cursor
+    ClearSelection,
+    /// Moves the cursor to `cursor`, removing the selection
+    SetCursor(Cursor),
+    /// Moves the cursor like the arrow keys would. If `select` is `true`
+    /// the selection is extended, like holding Shift
+    MoveCursor {
+        motion: Motion,
+        select: bool,
+    },
+    SelectAll,
+    /// Copies the selection to the system clipboard
+    Copy,
+    /// Moves the selection to the system clipboard
+    Cut,
+    /// Inserts the system clipboard at the cursor
+    Paste,
+}
+
+impl EditCommand {
+    /// Whether this command changes the text, and so isn't allowed on [`ReadOnly`] widgets
+    pub fn edits_text(&self) -> bool {
+        matches!(
+            self,
+            EditCommand::InsertText(_)
+                | EditCommand::ReplaceRange { .. }
+                | EditCommand::DeleteRange { .. }
+                | EditCommand::Cut
+                | EditCommand::Paste
+        )
+    }
+}
+
+/// Applies an [`EditCommand`] to a widget.
+///
+/// Text edits respect [`ReadOnly`], [`MaxChars`](crate::MaxChars),
+/// [`MaxLines`](crate::MaxLines) and [`InputFilter`](crate::filter::InputFilter).
+#[derive(EntityEvent, Debug, Clone)]
+pub struct CosmicEditCommand {
+    /// The widget to edit
+    pub entity: Entity,
+    pub command: EditCommand,
+}
+
+impl CosmicEditCommand {
+    pub fn new(entity: Entity, command: EditCommand) -> Self {
+        Self { entity, command }
+    }
+}
+
+fn apply_edit_command(
+    event: On<CosmicEditCommand>,
+    mut widgets: Query<(
+        EditorBuffer,
+        &mut EditHistory,
+        InsertLimits,
+        Option<&ReadOnly>,
+    )>,
+    mut commands: Commands,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut edits: TextEditWriter,
+    #[cfg(target_arch = "wasm32")] channel: Option<Res<clipboard::WasmPasteAsyncChannel>>,
+) {
+    let entity = event.entity;
+    let Ok((mut buffer, mut history, limits, readonly_opt)) = widgets.get_mut(entity) else {
+        warn!(
+            ?entity,
+            "Edit command targeted an entity without a `CosmicEditBuffer`"
+        );
+        return;
+    };
+    if readonly_opt.is_some() && event.command.edits_text() {
+        debug!(?entity, command = ?event.command, "Ignoring edit command on `ReadOnly` widget");
+        return;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    if event.command == EditCommand::Paste {
+        if let Some(channel) = channel {
+            clipboard::request_wasm_paste(&channel, entity);
+        }
+        return;
+    }
+
+    let font_system = &mut font_system.0;
+    let mut rejected = String::new();
+    let changed = buffer.with_editor_mut(|editor| {
+        history.begin(editor);
+        let origin = match &event.command {
+            EditCommand::InsertText(text) => {
+                rejected = insert_text_limited(editor, font_system, text, &limits);
+                EditOrigin::Programmatic
+            }
+            EditCommand::ReplaceRange { start, end, text } => {
+                select_range(editor, *start, *end);
+                rejected = insert_text_limited(editor, font_system, text, &limits);
+                EditOrigin::Programmatic
+            }
+            EditCommand::DeleteRange { start, end } => {
+                if select_range(editor, *start, *end) {
+                    delete_limited(editor, font_system, false, &limits);
+                } else {
+                    // Backspace would delete the character before the cursor
+                    editor.set_selection(Selection::None);
+                }
+                EditOrigin::Programmatic
+            }
+            EditCommand::SetSelection { anchor, cursor } => {
+                let (anchor, cursor) = editor.with_buffer(|buffer| {
+                    (clamp_cursor(buffer, *anchor), clamp_cursor(buffer, *cursor))
+                });
+                editor.set_cursor(cursor);
+                editor.set_selection(Selection::Normal(anchor));
+                EditOrigin::Programmatic
+            }
+            EditCommand::ClearSelection => {
+                editor.set_selection(Selection::None);
+                EditOrigin::Programmatic
+            }
+            EditCommand::SetCursor(cursor) => {
+                let cursor = editor.with_buffer(|buffer| clamp_cursor(buffer, *cursor));
+                editor.set_cursor(cursor);
+                editor.set_selection(Selection::None);
+                EditOrigin::Programmatic
+            }
+            EditCommand::MoveCursor { motion, select } => {
+                if *select && editor.selection() == Selection::None {
+                    let cursor = editor.cursor();
+                    editor.set_selection(Selection::Normal(cursor));
+                }
+                editor.action(font_system, Action::Motion(*motion));
+                if !*select {
+                    editor.set_selection(Selection::None);
+                }
+                EditOrigin::Programmatic
+            }
+            EditCommand::SelectAll => {
+                editor.action(font_system, Action::Motion(Motion::BufferEnd));
+                editor.set_selection(Selection::Normal(Cursor::new(0, 0)));
+                EditOrigin::Programmatic
+            }
+            EditCommand::Copy => {
+                if let Some(text) = editor.copy_selection() {
+                    clipboard::write_clipboard(text);
+                }
+                EditOrigin::Programmatic
+            }
+            EditCommand::Cut => {
+                if let Some(text) = editor.copy_selection() {
+                    clipboard::write_clipboard(text);
+                    delete_limited(editor, font_system, false, &limits);
+                }
+                EditOrigin::Cut
+            }
+            EditCommand::Paste => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(text) = clipboard::read_clipboard() {
+                    rejected = insert_text_limited(editor, font_system, &text, &limits);
+                }
+                EditOrigin::Paste
+            }
+        };
+        editor.set_redraw(true);
+        history.commit(editor, origin)
+    });
+
+    if !rejected.is_empty() {
+        commands.trigger(InputRejected {
+            entity,
+            text: rejected,
+        });
+    }
+    if changed {
+        edits.write(entity, &mut history, || buffer.get_text());
+    }
+}
+
+/// Selects between two positions given in any order.
+///
+/// Returns `false` if the range is empty
+fn select_range<'b>(editor: &mut impl Edit<'b>, start: Cursor, end: Cursor) -> bool {
+    let (start, end) = editor.with_buffer(|buffer| {
+        let start = clamp_cursor(buffer, start);
+        let end = clamp_cursor(buffer, end);
+        if (end.line, end.index) < (start.line, start.index) {
+            (end, start)
+        } else {
+            (start, end)
+        }
+    });
+    editor.set_cursor(end);
+    editor.set_selection(Selection::Normal(start));
+    (start.line, start.index) != (end.line, end.index)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{Attrs, Metrics};
+
+    use super::*;
+    #[allow(deprecated)]
+    use crate::input::CosmicTextChanged;
+    use crate::{
+        change::{CosmicTextEdited, EditKind, ReportFullText},
+        filter::InputFilter,
+        MaxChars,
+    };
+
+    #[allow(deprecated)]
+    fn test_app(text: &str, focused: bool) -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_message::<CosmicTextChanged>()
+            .add_message::<CosmicTextEdited>()
+            .add_plugins(plugin);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.)).with_text(
+            &mut font_system,
+            text,
+            Attrs::new(),
+        );
+        let entity = if focused {
+            let editor = CosmicEditor::clone_from_buffer(&buffer);
+            app.world_mut().spawn((buffer, editor, MaxChars(8))).id()
+        } else {
+            app.world_mut().spawn((buffer, MaxChars(8))).id()
+        };
+        app.insert_resource(CosmicFontSystem(font_system));
+
+        (app, entity)
+    }
+
+    fn run(text: &str, focused: bool, commands: impl IntoIterator<Item = EditCommand>) -> App {
+        let (mut app, entity) = test_app(text, focused);
+        for command in commands {
+            app.world_mut()
+                .trigger(CosmicEditCommand::new(entity, command));
+        }
+        app
+    }
+
+    fn text(app: &mut App) -> String {
+        let mut q = app.world_mut().query::<EditorBuffer>();
+        q.single_mut(app.world_mut()).unwrap().get_text()
+    }
+
+    #[test]
+    fn focused_and_unfocused_match() {
+        for focused in [true, false] {
+            let mut app = run(
+                "hello",
+                focused,
+                [
+                    EditCommand::MoveCursor {
+                        motion: Motion::BufferEnd,
+                        select: false,
+                    },
+                    EditCommand::InsertText(" world".into()),
+                    EditCommand::ReplaceRange {
+                        start: Cursor::new(0, 0),
+                        end: Cursor::new(0, 1),
+                        text: "J".into(),
+                    },
+                ],
+            );
+            // MaxChars(8) cuts off the insertion
+            assert_eq!(text(&mut app), "Jello wo", "focused: {focused}");
+        }
+    }
+
+    #[test]
+    fn empty_delete_range_deletes_nothing() {
+        for focused in [true, false] {
+            let mut app = run(
+                "hello",
+                focused,
+                [EditCommand::DeleteRange {
+                    start: Cursor::new(0, 3),
+                    end: Cursor::new(0, 3),
+                }],
+            );
+            assert_eq!(text(&mut app), "hello", "focused: {focused}");
+        }
+    }
+
+    #[test]
+    fn read_only_only_moves() {
+        let (mut app, entity) = test_app("hello", false);
+        app.world_mut().entity_mut(entity).insert(ReadOnly);
+        app.world_mut().trigger(CosmicEditCommand::new(
+            entity,
+            EditCommand::DeleteRange {
+                start: Cursor::new(0, 0),
+                end: Cursor::new(0, 5),
+            },
+        ));
+        app.world_mut()
+            .trigger(CosmicEditCommand::new(entity, EditCommand::SelectAll));
+
+        assert_eq!(text(&mut app), "hello");
+        assert_eq!(
+            app.world()
+                .get::<crate::buffer::SavedCursor>(entity)
+                .unwrap()
+                .selection(),
+            Selection::Normal(Cursor::new(0, 0))
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn sends_text_edited() {
+        let (mut app, entity) = test_app("hello", false);
+        app.world_mut().entity_mut(entity).insert(ReportFullText);
+        app.world_mut().trigger(CosmicEditCommand::new(
+            entity,
+            EditCommand::InsertText("Oh ".into()),
+        ));
+        assert_eq!(text(&mut app), "Oh hello");
+
+        let edited = app
+            .world()
+            .resource::<Messages<CosmicTextEdited>>()
+            .iter_current_update_messages()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(edited.len(), 1);
+        assert_eq!(edited[0].kind, EditKind::Insert);
+        assert_eq!(edited[0].origin, EditOrigin::Programmatic);
+        assert_eq!(edited[0].inserted, "Oh ");
+        assert_eq!(edited[0].text.as_deref(), Some("Oh hello"));
+
+        let changed = app
+            .world()
+            .resource::<Messages<CosmicTextChanged>>()
+            .iter_current_update_messages()
+            .map(|CosmicTextChanged((_, text))| text.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(changed, ["Oh hello"]);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn text_changed_is_not_sent_without_full_text() {
+        let (mut app, entity) = test_app("hello", false);
+        app.world_mut().trigger(CosmicEditCommand::new(
+            entity,
+            EditCommand::InsertText("Oh ".into()),
+        ));
+
+        let edited = app
+            .world()
+            .resource::<Messages<CosmicTextEdited>>()
+            .iter_current_update_messages()
+            .map(|edited| edited.text.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(edited, [None]);
+
+        let changed = app
+            .world()
+            .resource::<Messages<CosmicTextChanged>>()
+            .iter_current_update_messages()
+            .count();
+        assert_eq!(changed, 0);
+    }
+
+    #[test]
+    fn filter_rejects_characters() {
+        let (mut app, entity) = test_app("", false);
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(InputFilter::Integer)
+            .observe(|rejected: On<InputRejected>, mut commands: Commands| {
+                commands.insert_resource(Rejected(rejected.text.clone()));
+            });
+        app.world_mut().trigger(CosmicEditCommand::new(
+            entity,
+            EditCommand::InsertText("-1a2-3".into()),
+        ));
+        app.world_mut().flush();
+
+        assert_eq!(text(&mut app), "-123");
+        assert_eq!(app.world().resource::<Rejected>().0, "a-");
+    }
+
+    #[derive(Resource)]
+    struct Rejected(String);
+}
@@ -1,16 +1,23 @@
-use crate::prelude::*;
+use crate::{input::InputState, prelude::*};
+use bevy::ecs::{lifecycle::HookContext, world::DeferredWorld};
 use cosmic_text::{Align, Attrs, AttrsOwned, FontSystem};
 
 pub(crate) fn plugin(app: &mut App) {
     app.register_type::<CosmicWrap>()
         .register_type::<CosmicTextAlign>()
+        .register_type::<CosmicPadding>()
         .register_type::<CosmicBackgroundImage>()
         .register_type::<CosmicBackgroundColor>()
         .register_type::<CursorColor>()
         .register_type::<SelectionColor>()
+        .register_type::<InactiveSelectionColor>()
         .register_type::<MaxLines>()
         .register_type::<MaxChars>()
-        .register_type::<ScrollEnabled>();
+        .register_type::<ScrollEnabled>()
+        .register_type::<Disabled>()
+        .register_type::<CosmicRenderMode>()
+        .register_type::<RenderThreads>()
+        .init_resource::<RenderThreads>();
 }
 
 /// Enum representing text wrapping in a cosmic [`Buffer`]
@@ -104,6 +111,46 @@ impl CosmicTextAlign {
     }
 }
 
+/// Space between the edges of a widget and its text, in logical pixels.
+///
+/// Text is laid out, wrapped and aligned within the remaining area, and clipped to it
+/// when scrolling. Defaults to no padding
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq)]
+pub struct CosmicPadding {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+    /// Adds the padding and border of a [`TextEdit`]'s [`Node`] on top
+    pub include_node: bool,
+}
+
+impl CosmicPadding {
+    pub fn all(padding: f32) -> Self {
+        Self::axes(padding, padding)
+    }
+
+    pub fn axes(horizontal: f32, vertical: f32) -> Self {
+        Self {
+            left: horizontal,
+            right: horizontal,
+            top: vertical,
+            bottom: vertical,
+            include_node: false,
+        }
+    }
+
+    /// Only the padding and border of a [`TextEdit`]'s [`Node`]
+    pub fn node() -> Self {
+        Self::default().with_node()
+    }
+
+    pub fn with_node(mut self) -> Self {
+        self.include_node = true;
+        self
+    }
+}
+
 /// Enum representing the text alignment in a cosmic [`Buffer`].
 /// Defaults to [`CosmicTextAlign::Center`]
 #[derive(Reflect, Default, Clone, Copy, PartialEq, Eq)]
@@ -144,6 +191,29 @@ impl From<HorizontalAlign> for Align {
 #[derive(Component, Default)]
 pub struct ReadOnly; // tag component
 
+/// Tag component to disable a widget entirely.
+///
+/// Unlike [`ReadOnly`], a disabled widget can't be focused, hovered or selected,
+/// ignores all pointer input and is drawn with half transparent text.
+/// Adding it to the focused widget unfocuses it.
+///
+/// Not to be confused with bevy's `Disabled`, which hides entities from queries
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+#[component(on_insert = disable_widget)]
+pub struct Disabled;
+
+fn disable_widget(mut world: DeferredWorld, HookContext { entity, .. }: HookContext) {
+    if let Some(mut focused_widget) = world.get_resource_mut::<FocusedWidget>() {
+        if focused_widget.0 == Some(entity) {
+            focused_widget.0 = None;
+        }
+    }
+    if let Some(mut input_state) = world.get_mut::<InputState>(entity) {
+        *input_state = InputState::Idle;
+    }
+}
+
 /// Default text attributes to be used on a [`CosmicEditBuffer`]
 #[derive(Component, Deref, DerefMut)]
 pub struct DefaultAttrs(pub AttrsOwned);
@@ -188,13 +258,25 @@ impl Default for SelectionColor {
 #[derive(Component, Reflect, Default, Deref)]
 pub struct SelectedTextColor(pub Color);
 
+/// Keeps showing the selection of an unfocused widget, using this color
+/// instead of [`SelectionColor`]. Usually a dimmer version of it
+#[derive(Component, Reflect, Deref)]
+pub struct InactiveSelectionColor(pub Color);
+
+impl Default for InactiveSelectionColor {
+    fn default() -> Self {
+        InactiveSelectionColor(bevy::color::palettes::basic::GRAY.with_alpha(0.5).into())
+    }
+}
+
 /// Maximum number of lines allowed in a buffer
 // TODO: Actually test this? I'm not sure this does anything afaik
 #[derive(Component, Reflect, Default)]
 pub struct MaxLines(pub usize);
 
 /// Maximum number of characters allowed in a buffer
-// TODO: Check this functionality with widechars; Use graphemes to test?
+///
+/// Counted in grapheme clusters, so "é" or "👍🏽" count as a single character
 #[derive(Component, Reflect, Default)]
 pub struct MaxChars(pub usize);
 
@@ -208,6 +290,40 @@ impl Default for ScrollEnabled {
     }
 }
 
+/// How a widget draws its text. Defaults to [`CosmicRenderMode::Texture`]
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum CosmicRenderMode {
+    /// Rasterizes everything on the CPU into the image of the widget,
+    /// which is uploaded to the GPU again whenever it changes, see [`RenderThreads`]
+    #[default]
+    Texture,
+    /// Draws every glyph as a quad from a texture atlas shared by all widgets,
+    /// like bevy's own text. The selection and cursor are quads too.
+    ///
+    /// The image of the widget only holds the background, so editing text doesn't
+    /// upload any textures, except when new glyphs are added to the atlas.
+    /// The quads are child entities of the widget
+    GlyphAtlas,
+}
+
+/// Where widgets are rasterized into their images.
+///
+/// Text is always laid out on the main thread, since that needs the [`CosmicFontSystem`].
+/// Either way, every widget looks exactly the same
+#[derive(Resource, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum RenderThreads {
+    /// Widgets that need redrawing are rasterized in parallel on the
+    /// [`ComputeTaskPool`](bevy::tasks::ComputeTaskPool), each thread with its own glyph cache.
+    ///
+    /// Without the `multi_threaded` feature this is the same as [`RenderThreads::MainThread`]
+    #[default]
+    TaskPool,
+    /// Widgets are rasterized one after another on the main thread
+    MainThread,
+}
+
 /// Holds the font system used internally by [`cosmic_text`]
 ///
 /// Note: When bevy provides enough initialisation flexibility,
@@ -4,17 +4,24 @@
 //! of [`CosmicEditor`], which is the primary interface for mutating [`Buffer`].
 
 use bevy::ecs::query::QueryData;
-use cosmic_text::{Attrs, BufferRef, FontSystem, Shaping};
+use cosmic_text::{Attrs, BufferRef, Cursor, Editor, FontSystem, Selection, Shaping};
 
-use crate::prelude::*;
+use crate::{prelude::*, render::RenderSet};
 
 pub(crate) fn plugin(app: &mut App) {
-    app.add_systems(First, (buffer::set_initial_scale, buffer::add_font_system))
+    app.add_systems(First, buffer::add_font_system)
+        .add_systems(
+            PostUpdate,
+            buffer::update_scale_factor
+                .after(bevy::ui::UiSystems::Layout)
+                .before(RenderSet),
+        )
         .add_systems(Update, editor::blink_cursor);
 }
 
 pub mod buffer;
 pub mod editor;
+pub mod history;
 
 /// Primary interface for accessing the [`cosmic_text::Buffer`] of a widget.
 ///
@@ -31,6 +38,7 @@ pub mod editor;
 pub struct EditorBuffer {
     editor: Option<&'static mut CosmicEditor>,
     buffer: &'static mut CosmicEditBuffer,
+    saved_cursor: &'static mut buffer::SavedCursor,
 }
 
 impl std::ops::Deref for EditorBufferItem<'_, '_> {
@@ -52,6 +60,11 @@ impl<'r, 's> EditorBufferItem<'_, '_> {
         self.editor.as_deref_mut()
     }
 
+    /// The [`CosmicEditor`], without marking it as changed
+    pub fn get_editor(&self) -> Option<&CosmicEditor> {
+        self.editor.as_deref()
+    }
+
     /// Replace buffer text
     pub fn set_text(
         &mut self,
@@ -83,6 +96,28 @@ impl<'r, 's> EditorBufferItem<'_, '_> {
         self
     }
 
+    /// Cursor of the [`CosmicEditor`], or the [`SavedCursor`](buffer::SavedCursor) if unfocused
+    pub fn cursor(&self) -> Cursor {
+        match self.editor.as_ref() {
+            Some(editor) => editor.cursor(),
+            None => self.saved_cursor.cursor,
+        }
+    }
+
+    /// Selection of the [`CosmicEditor`], or the [`SavedCursor`](buffer::SavedCursor) if unfocused
+    pub fn selection(&self) -> Selection {
+        match self.editor.as_ref() {
+            Some(editor) => editor.selection(),
+            None => self.saved_cursor.selection,
+        }
+    }
+
+    /// The selected text, `None` if nothing is selected
+    pub fn selected_text(&mut self) -> Option<String> {
+        self.with_editor_mut(|editor| editor.copy_selection())
+            .filter(|text| !text.is_empty())
+    }
+
     pub fn with_buffer_mut<F: FnOnce(&mut Buffer) -> T, T>(&mut self, f: F) -> T {
         match self.editor.as_mut() {
             Some(editor) => editor.with_buffer_mut(f),
@@ -90,6 +125,46 @@ impl<'r, 's> EditorBufferItem<'_, '_> {
         }
     }
 
+    /// Runs `f` with an [`Editor`] for this widget.
+    ///
+    /// Uses the [`CosmicEditor`] if there is one, otherwise the [`CosmicEditBuffer`]
+    /// is temporarily wrapped in a new [`Editor`], using and updating the
+    /// [`SavedCursor`](buffer::SavedCursor) of the widget.
+    /// This makes it possible to use [`cosmic_text::Edit`] methods regardless of focus.
+    pub fn with_editor_mut<F: FnOnce(&mut Editor<'static>) -> T, T>(&mut self, f: F) -> T {
+        match self.editor.as_mut() {
+            Some(editor) => f(&mut editor.editor),
+            None => {
+                let metrics = self.buffer.0.metrics();
+                let buffer = std::mem::replace(&mut self.buffer.0, Buffer::new_empty(metrics));
+                let mut editor = Editor::new(buffer);
+                let (cursor, selection) = editor.with_buffer(|buffer| {
+                    (
+                        buffer::clamp_cursor(buffer, self.saved_cursor.cursor),
+                        buffer::clamp_selection(buffer, self.saved_cursor.selection),
+                    )
+                });
+                editor.set_cursor(cursor);
+                editor.set_selection(selection);
+
+                let ret = f(&mut editor);
+
+                self.saved_cursor.cursor = editor.cursor();
+                self.saved_cursor.selection = editor.selection();
+                let buffer_ref = std::mem::replace(
+                    editor.buffer_ref_mut(),
+                    BufferRef::Owned(Buffer::new_empty(metrics)),
+                );
+                self.buffer.0 = match buffer_ref {
+                    BufferRef::Owned(buffer) => buffer,
+                    BufferRef::Borrowed(buffer) => buffer.clone(),
+                    BufferRef::Arc(arc) => std::sync::Arc::unwrap_or_clone(arc),
+                };
+                ret
+            }
+        }
+    }
+
     pub fn with_buffer<F: FnOnce(&Buffer) -> T, T>(&self, f: F) -> T {
         match self.editor.as_ref() {
             Some(editor) => editor.with_buffer(f),
@@ -5,12 +5,15 @@
 use cosmic_text::Attrs;
 use cosmic_text::AttrsOwned;
 use cosmic_text::BorrowedWithFontSystem;
+use cosmic_text::Cursor;
 use cosmic_text::FontSystem;
 use cosmic_text::Metrics;
+use cosmic_text::Selection;
 use cosmic_text::Shaping;
 
 use crate::cosmic_edit::*;
 use crate::prelude::*;
+use crate::render_implementations::{WidgetScaleFactor, WidgetWindows};
 
 pub trait BufferRefExtras {
     fn get_text(&self) -> String;
@@ -95,10 +98,9 @@ impl BufferMutExtras for BorrowedWithFontSystem<'_, cosmic_text::Editor<'_>> {
 ///
 #[derive(Component, Debug)]
 #[component(on_add = on_buffer_add, on_remove = crate::focus::remove_focus_from_entity)]
+// `CosmicBackgroundColor`, `CursorColor`, `SelectionColor` and `HoverCursor` are added
+// by `on_buffer_add`, so the theme can tell which ones were given
 #[require(
-    CosmicBackgroundColor,
-    CursorColor,
-    SelectionColor,
     DefaultAttrs,
     CosmicBackgroundImage,
     CosmicRenderOutput,
@@ -106,11 +108,70 @@ impl BufferMutExtras for BorrowedWithFontSystem<'_, cosmic_text::Editor<'_>> {
     MaxChars,
     CosmicWrap,
     CosmicTextAlign,
-    crate::input::hover::HoverCursor,
-    crate::input::InputState
+    super::history::EditHistory,
+    SavedCursor,
+    crate::input::ime::ImePreedit,
+    crate::selection::TrackedCursor,
+    crate::input::InputState,
+    WidgetScaleFactor,
+    crate::render::RenderCache
 )]
 pub struct CosmicEditBuffer(pub(super) Buffer);
 
+/// Cursor and selection of a widget while it doesn't have a [`CosmicEditor`].
+///
+/// Used by [`EditorBufferItem::with_editor_mut`](crate::EditorBufferItem::with_editor_mut),
+/// so editing an unfocused widget from code behaves like editing a focused one.
+/// The scroll position is kept in the [`CosmicEditBuffer`] itself.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedCursor {
+    pub(crate) cursor: Cursor,
+    pub(crate) selection: Selection,
+}
+
+impl Default for SavedCursor {
+    fn default() -> Self {
+        Self {
+            cursor: Cursor::new(0, 0),
+            selection: Selection::None,
+        }
+    }
+}
+
+impl SavedCursor {
+    pub fn cursor(&self) -> Cursor {
+        self.cursor
+    }
+
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+}
+
+/// Moves `cursor` to the closest position that exists in `buffer`
+pub(crate) fn clamp_cursor(buffer: &Buffer, cursor: Cursor) -> Cursor {
+    let Some(last_line) = buffer.lines.len().checked_sub(1) else {
+        return Cursor::new(0, 0);
+    };
+    let line = cursor.line.min(last_line);
+    let text = buffer.lines[line].text();
+    let mut index = cursor.index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    Cursor::new_with_affinity(line, index, cursor.affinity)
+}
+
+/// [`clamp_cursor`] for the anchor of a selection
+pub(crate) fn clamp_selection(buffer: &Buffer, selection: Selection) -> Selection {
+    match selection {
+        Selection::None => Selection::None,
+        Selection::Normal(anchor) => Selection::Normal(clamp_cursor(buffer, anchor)),
+        Selection::Line(anchor) => Selection::Line(clamp_cursor(buffer, anchor)),
+        Selection::Word(anchor) => Selection::Word(clamp_cursor(buffer, anchor)),
+    }
+}
+
 impl Default for CosmicEditBuffer {
     fn default() -> Self {
         CosmicEditBuffer(Buffer::new_empty(Metrics::new(20., 20.)))
@@ -127,6 +188,8 @@ fn on_buffer_add(
         .unwrap()
         .0
         .set_redraw(true);
+
+    crate::theme::insert_themed_components(&mut world, target);
 }
 
 /// Should be partly mirrored on [`EditorBuffer`]
@@ -203,6 +266,15 @@ impl<'s, 'r> CosmicEditBuffer {
         self
     }
 
+    /// See [`Buffer::redraw`]
+    pub(crate) fn redraw(&self) -> bool {
+        self.0.redraw()
+    }
+
+    pub(crate) fn set_redraw(&mut self, redraw: bool) {
+        self.0.set_redraw(redraw);
+    }
+
     pub fn from_raw_buffer(mut buffer: Buffer) -> CosmicEditBuffer {
         buffer.set_redraw(true);
         Self(buffer)
@@ -271,18 +343,88 @@ pub(in crate::editor_buffer) fn add_font_system(
     }
 }
 
-/// Initialises [`CosmicEditBuffer`] scale factor
-pub(in crate::editor_buffer) fn set_initial_scale(
-    window_q: Query<&Window, With<bevy::window::PrimaryWindow>>,
-    mut cosmic_query: Query<&mut CosmicEditBuffer, Added<CosmicEditBuffer>>,
+/// Keeps the [`WidgetScaleFactor`] of widgets up to date with their window's scale factor
+/// (or the UI scale), rescaling the buffer metrics to match.
+///
+/// Runs every frame, so [`WindowScaleFactorChanged`](bevy::window::WindowScaleFactorChanged)
+/// and widgets moving between windows are picked up
+pub(in crate::editor_buffer) fn update_scale_factor(
+    mut widgets: Query<(
+        Entity,
+        EditorBuffer,
+        &mut WidgetScaleFactor,
+        Option<&ComputedNode>,
+        Has<TextEdit>,
+    )>,
+    windows: WidgetWindows,
     mut font_system: ResMut<CosmicFontSystem>,
 ) {
-    if let Ok(window) = window_q.single() {
-        let w_scale = window.scale_factor();
-
-        for mut b in &mut cosmic_query.iter_mut() {
-            let m = b.0.metrics().scale(w_scale);
-            b.0.set_metrics(&mut font_system, m);
+    for (entity, mut editor, mut scale_factor, ui_node, is_ui) in widgets.iter_mut() {
+        let scale = match (is_ui, ui_node) {
+            (true, Some(node)) if node.inverse_scale_factor() > 0. => {
+                node.inverse_scale_factor().recip()
+            }
+            (true, _) => continue,
+            (false, _) => windows.scale_factor_of(entity).unwrap_or(1.),
+        };
+        if scale_factor.0 == scale {
+            continue;
         }
+        let metrics = editor.metrics().scale(scale / scale_factor.0);
+        editor.set_metrics(&mut font_system.0, metrics);
+        scale_factor.0 = scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::window::{PrimaryWindow, WindowResolution};
+    use cosmic_text::Metrics;
+
+    use super::*;
+
+    #[test]
+    fn metrics_follow_window_scale_factor() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_systems(Update, update_scale_factor);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 24.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let window = app
+            .world_mut()
+            .spawn((
+                Window {
+                    resolution: WindowResolution::new(200, 100).with_scale_factor_override(2.),
+                    ..default()
+                },
+                PrimaryWindow,
+            ))
+            .id();
+        let widget = app.world_mut().spawn((TextEdit2d, buffer)).id();
+        let metrics = |app: &App| {
+            app.world()
+                .get::<CosmicEditBuffer>(widget)
+                .unwrap()
+                .inner()
+                .metrics()
+        };
+
+        app.update();
+        assert_eq!(metrics(&app), Metrics::new(40., 48.));
+        assert_eq!(
+            app.world().get::<WidgetScaleFactor>(widget),
+            Some(&WidgetScaleFactor(2.))
+        );
+
+        // e.g. after the window moved to another monitor
+        app.world_mut()
+            .get_mut::<Window>(window)
+            .unwrap()
+            .resolution
+            .set_scale_factor_override(Some(1.5));
+        app.update();
+        assert_eq!(metrics(&app), Metrics::new(30., 36.));
     }
 }
@@ -0,0 +1,502 @@
+//! Undo / redo history for [`CosmicEditBuffer`] widgets.
+//!
+//! Every widget has an [`EditHistory`] component. It lives next to the
+//! [`CosmicEditBuffer`] rather than inside the [`CosmicEditor`], so the history
+//! survives the widget losing and regaining focus.
+//!
+//! Edits are recorded using [`cosmic_text`]'s own change tracking
+//! ([`Edit::start_change`](cosmic_text::Edit::start_change)), so anything done
+//! to the editor between [`EditHistory::begin`] and [`EditHistory::commit`]
+//! becomes one undo step.
+
+use std::collections::VecDeque;
+
+use cosmic_text::{Change, Cursor, Edit, Selection};
+
+use crate::prelude::*;
+
+/// Where an edit came from.
+///
+/// Consecutive [`EditOrigin::Typing`] or [`EditOrigin::Deleting`] edits
+/// are grouped into a single undo step.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditOrigin {
+    /// Characters typed with the keyboard
+    Typing,
+    /// Backspace / Delete
+    Deleting,
+    Paste,
+    Cut,
+    /// Edits made through code
+    Programmatic,
+    /// [`EditHistory::undo`]
+    Undo,
+    /// [`EditHistory::redo`]
+    Redo,
+}
+
+impl EditOrigin {
+    fn groups(self) -> bool {
+        matches!(self, EditOrigin::Typing | EditOrigin::Deleting)
+    }
+}
+
+/// One undo step
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    change: Change,
+    origin: EditOrigin,
+    cursor_before: Cursor,
+    selection_before: Selection,
+    cursor_after: Cursor,
+    selection_after: Selection,
+}
+
+/// Per-widget undo / redo stack.
+///
+/// Keyboard shortcuts (`Ctrl+Z`, `Ctrl+Shift+Z` and `Ctrl+Y`, or `Cmd` on macOS)
+/// are handled for the focused widget. To drive the history from code, use
+/// [`EditorBufferItem::with_editor_mut`](crate::EditorBufferItem::with_editor_mut)
+/// so it works whether or not the widget is focused:
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_cosmic_edit::prelude::*;
+/// fn undo_all(mut q: Query<(EditorBuffer, &mut EditHistory)>) {
+///     for (mut buffer, mut history) in q.iter_mut() {
+///         buffer.with_editor_mut(|editor| while history.undo(editor) {});
+///     }
+/// }
+/// ```
+#[derive(Component, Debug)]
+pub struct EditHistory {
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    max_depth: usize,
+    /// Cursor and selection captured by [`EditHistory::begin`]
+    pending: Option<(Cursor, Selection)>,
+    /// Stops the next edit from being merged into the last undo step
+    sealed: bool,
+    /// The change made by the last commit, undo or redo, until it is reported
+    last_edit: Option<(Change, EditOrigin)>,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::with_max_depth(100)
+    }
+}
+
+impl EditHistory {
+    /// Keep at most `max_depth` undo steps. `0` means unlimited
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_depth,
+            pending: None,
+            sealed: false,
+            last_edit: None,
+        }
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Changes the depth limit, dropping the oldest steps if there are too many
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+        self.enforce_depth();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forget all undo and redo steps
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.sealed = false;
+    }
+
+    /// Makes sure the next edit starts a new undo step,
+    /// instead of being grouped with the previous one
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Start recording an edit. Every change made to `editor` until
+    /// [`EditHistory::commit`] is called becomes part of the same undo step.
+    pub fn begin<'b>(&mut self, editor: &mut impl Edit<'b>) {
+        // drop anything left over from an edit that was never committed
+        editor.finish_change();
+        editor.start_change();
+        self.pending = Some((editor.cursor(), editor.selection()));
+        self.last_edit = None;
+    }
+
+    /// Finish recording an edit started with [`EditHistory::begin`].
+    ///
+    /// Returns `true` if the buffer text actually changed.
+    pub fn commit<'b>(&mut self, editor: &mut impl Edit<'b>, origin: EditOrigin) -> bool {
+        let change = editor.finish_change();
+        let Some((cursor_before, selection_before)) = self.pending.take() else {
+            return false;
+        };
+        let Some(change) = change.filter(|change| !change.items.is_empty()) else {
+            return false;
+        };
+
+        let entry = HistoryEntry {
+            change,
+            origin,
+            cursor_before,
+            selection_before,
+            cursor_after: editor.cursor(),
+            selection_after: editor.selection(),
+        };
+
+        self.last_edit = Some((entry.change.clone(), origin));
+        self.redo.clear();
+        match self.undo.back_mut() {
+            Some(last) if !self.sealed && should_group(last, &entry) => {
+                last.change.items.extend(entry.change.items);
+                last.cursor_after = entry.cursor_after;
+                last.selection_after = entry.selection_after;
+            }
+            _ => {
+                self.undo.push_back(entry);
+                self.enforce_depth();
+            }
+        }
+        self.sealed = false;
+
+        true
+    }
+
+    /// Reverts the last undo step, restoring the cursor and selection from before it.
+    ///
+    /// Returns `false` if there was nothing to undo
+    pub fn undo<'b>(&mut self, editor: &mut impl Edit<'b>) -> bool {
+        let Some(entry) = self.undo.pop_back() else {
+            return false;
+        };
+
+        let mut change = entry.change.clone();
+        change.reverse();
+        if !apply_change(editor, &change) {
+            warn!("Buffer was changed outside of the edit history, clearing it");
+            self.clear();
+            return false;
+        }
+        editor.set_cursor(entry.cursor_before);
+        editor.set_selection(entry.selection_before);
+
+        self.last_edit = Some((change, EditOrigin::Undo));
+        self.redo.push(entry);
+        self.sealed = true;
+        true
+    }
+
+    /// Re-applies the last undone step.
+    ///
+    /// Returns `false` if there was nothing to redo
+    pub fn redo<'b>(&mut self, editor: &mut impl Edit<'b>) -> bool {
+        let Some(entry) = self.redo.pop() else {
+            return false;
+        };
+
+        if !apply_change(editor, &entry.change) {
+            warn!("Buffer was changed outside of the edit history, clearing it");
+            self.clear();
+            return false;
+        }
+        editor.set_cursor(entry.cursor_after);
+        editor.set_selection(entry.selection_after);
+
+        self.last_edit = Some((entry.change.clone(), EditOrigin::Redo));
+        self.undo.push_back(entry);
+        self.sealed = true;
+        true
+    }
+
+    /// Takes the change made by the last commit, undo or redo, to report it
+    pub(crate) fn take_last_edit(&mut self) -> Option<(Change, EditOrigin)> {
+        self.last_edit.take()
+    }
+
+    fn enforce_depth(&mut self) {
+        if self.max_depth == 0 {
+            return;
+        }
+        while self.undo.len() > self.max_depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+fn same_position(a: Cursor, b: Cursor) -> bool {
+    a.line == b.line && a.index == b.index
+}
+
+/// Typing "hello world" gives two undo steps, "hello" and " world"
+fn should_group(last: &HistoryEntry, next: &HistoryEntry) -> bool {
+    if !next.origin.groups() || last.origin != next.origin {
+        return false;
+    }
+    if !same_position(last.cursor_after, next.cursor_before) {
+        return false;
+    }
+    if next.origin == EditOrigin::Typing {
+        let last_ends_word = last
+            .change
+            .items
+            .last()
+            .and_then(|item| item.text.chars().last())
+            .is_some_and(|c| !c.is_whitespace());
+        let next_starts_space = next
+            .change
+            .items
+            .first()
+            .and_then(|item| item.text.chars().next())
+            .is_some_and(char::is_whitespace);
+        if last_ends_word && next_starts_space {
+            return false;
+        }
+    }
+    true
+}
+
+fn cursor_in_bounds(lines: &[String], cursor: Cursor) -> bool {
+    lines
+        .get(cursor.line)
+        .is_some_and(|line| line.is_char_boundary(cursor.index))
+}
+
+/// Checks every item of `change` against a copy of the text, applying the items before it,
+/// so a change that doesn't fit is rejected before any of it is applied
+fn change_in_bounds(buffer: &Buffer, change: &Change) -> bool {
+    let mut lines: Vec<String> = buffer
+        .lines
+        .iter()
+        .map(|line| line.text().to_owned())
+        .collect();
+    for item in &change.items {
+        let (start, end) = (item.start, item.end);
+        if !cursor_in_bounds(&lines, start) {
+            return false;
+        }
+        if item.insert {
+            let line = &lines[start.line];
+            let text = format!(
+                "{}{}{}",
+                &line[..start.index],
+                item.text,
+                &line[start.index..]
+            );
+            let inserted = text
+                .replace("\r\n", "\n")
+                .replace('\r', "\n")
+                .split('\n')
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            lines.splice(start.line..=start.line, inserted);
+        } else {
+            if !cursor_in_bounds(&lines, end) || (end.line, end.index) < (start.line, start.index) {
+                return false;
+            }
+            let after = lines[end.line][end.index..].to_owned();
+            lines.drain(start.line + 1..=end.line);
+            let line = &mut lines[start.line];
+            line.truncate(start.index);
+            line.push_str(&after);
+        }
+    }
+    true
+}
+
+/// Like [`Edit::apply_change`], but refuses to touch text that doesn't exist
+fn apply_change<'b>(editor: &mut impl Edit<'b>, change: &Change) -> bool {
+    if !editor.with_buffer(|buffer| change_in_bounds(buffer, change)) {
+        return false;
+    }
+    for item in &change.items {
+        if item.insert {
+            editor.insert_at(item.start, &item.text, None);
+        } else {
+            editor.delete_range(item.start, item.end);
+        }
+    }
+    editor.set_redraw(true);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{ChangeItem, Editor, FontSystem, Metrics};
+
+    use super::*;
+
+    fn editor() -> Editor<'static> {
+        let mut font_system = FontSystem::new_with_fonts([]);
+        Editor::new(Buffer::new(&mut font_system, Metrics::new(20., 20.)))
+    }
+
+    fn text(editor: &Editor) -> String {
+        editor.with_buffer(|buffer| {
+            buffer
+                .lines
+                .iter()
+                .map(|line| line.text())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+    }
+
+    fn edit(
+        history: &mut EditHistory,
+        editor: &mut Editor,
+        origin: EditOrigin,
+        f: impl FnOnce(&mut Editor),
+    ) {
+        history.begin(editor);
+        f(editor);
+        history.commit(editor, origin);
+    }
+
+    /// Types `text` one character at a time, like the keyboard does
+    fn type_text(history: &mut EditHistory, editor: &mut Editor, text: &str) {
+        for c in text.chars() {
+            edit(history, editor, EditOrigin::Typing, |editor| {
+                editor.insert_string(&c.to_string(), None);
+            });
+        }
+    }
+
+    #[test]
+    fn typing_is_grouped_by_word() {
+        let mut editor = editor();
+        let mut history = EditHistory::default();
+        type_text(&mut history, &mut editor, "hello world");
+
+        assert!(history.undo(&mut editor));
+        assert_eq!(text(&editor), "hello");
+        assert!(history.undo(&mut editor));
+        assert_eq!(text(&editor), "");
+        assert!(!history.undo(&mut editor));
+    }
+
+    #[test]
+    fn undo_and_redo_restore_cursor_and_selection() {
+        let mut editor = editor();
+        let mut history = EditHistory::default();
+        type_text(&mut history, &mut editor, "abc");
+
+        let selection = Selection::Normal(Cursor::new(0, 1));
+        editor.set_selection(selection);
+        edit(&mut history, &mut editor, EditOrigin::Cut, |editor| {
+            editor.delete_selection();
+        });
+        assert_eq!(text(&editor), "a");
+        let cursor_after = editor.cursor();
+
+        assert!(history.undo(&mut editor));
+        assert_eq!(text(&editor), "abc");
+        assert!(same_position(editor.cursor(), Cursor::new(0, 3)));
+        assert_eq!(editor.selection(), selection);
+
+        assert!(history.redo(&mut editor));
+        assert_eq!(text(&editor), "a");
+        assert_eq!(editor.cursor(), cursor_after);
+        assert_eq!(editor.selection(), Selection::None);
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut editor = editor();
+        let mut history = EditHistory::default();
+        type_text(&mut history, &mut editor, "a");
+        assert!(history.undo(&mut editor));
+        assert!(history.can_redo());
+
+        type_text(&mut history, &mut editor, "b");
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut editor));
+        assert_eq!(text(&editor), "b");
+    }
+
+    #[test]
+    fn max_depth_drops_oldest_steps() {
+        let mut editor = editor();
+        let mut history = EditHistory::with_max_depth(2);
+        for word in ["a", "b", "c"] {
+            edit(&mut history, &mut editor, EditOrigin::Paste, |editor| {
+                editor.insert_string(word, None);
+            });
+        }
+
+        assert!(history.undo(&mut editor));
+        assert!(history.undo(&mut editor));
+        assert!(!history.undo(&mut editor));
+        assert_eq!(text(&editor), "a");
+
+        history.redo(&mut editor);
+        history.redo(&mut editor);
+        history.set_max_depth(1);
+        assert!(history.undo(&mut editor));
+        assert!(!history.can_undo());
+        assert_eq!(text(&editor), "ab");
+    }
+
+    #[test]
+    fn out_of_range_changes_are_rejected() {
+        let mut editor = editor();
+        let change = Change {
+            items: vec![ChangeItem {
+                start: Cursor::new(1, 0),
+                end: Cursor::new(1, 1),
+                text: "x".into(),
+                insert: true,
+            }],
+        };
+        assert!(!apply_change(&mut editor, &change));
+        assert_eq!(text(&editor), "");
+
+        // nothing is applied when a later item doesn't fit
+        editor.insert_string("abc", None);
+        let change = Change {
+            items: vec![
+                ChangeItem {
+                    start: Cursor::new(0, 0),
+                    end: Cursor::new(0, 1),
+                    text: "a".into(),
+                    insert: false,
+                },
+                ChangeItem {
+                    start: Cursor::new(0, 1),
+                    end: Cursor::new(0, 3),
+                    text: "cd".into(),
+                    insert: false,
+                },
+            ],
+        };
+        assert!(!apply_change(&mut editor, &change));
+        assert_eq!(text(&editor), "abc");
+        editor.delete_range(Cursor::new(0, 0), Cursor::new(0, 3));
+        editor.set_cursor(Cursor::new(0, 0));
+
+        // the buffer was changed without the history knowing
+        let mut history = EditHistory::default();
+        type_text(&mut history, &mut editor, "abc");
+        editor.delete_range(Cursor::new(0, 0), Cursor::new(0, 3));
+        assert!(!history.undo(&mut editor));
+        assert!(!history.can_undo());
+        assert_eq!(text(&editor), "");
+    }
+}
@@ -0,0 +1,259 @@
+//! Restricting what can be typed into a widget.
+//!
+//! An [`InputFilter`] is checked for every character that is typed, pasted or inserted
+//! with an [`EditCommand`](crate::command::EditCommand). Characters that would make the
+//! text unacceptable are left out, and reported with an [`InputRejected`] event.
+//!
+//! Widgets with a filter also get an [`InputValidity`], which says whether the text is
+//! a complete value (e.g. `-` is acceptable while typing an integer, but not valid).
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::filter::{InputFilter, InputRejected, InputValidity};
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands
+//!         .spawn((TextEdit, CosmicEditBuffer::default(), InputFilter::Integer))
+//!         .observe(|rejected: On<InputRejected>| {
+//!             info!("Only digits please, not {:?}", rejected.text);
+//!         });
+//! }
+//!
+//! fn submit_button(fields: Query<&InputValidity>) -> bool {
+//!     fields.iter().all(|validity| *validity == InputValidity::Valid)
+//! }
+//! ```
+
+use std::sync::Arc;
+
+use crate::{
+    change::TextChanges, mask::InputMask, password::PasswordSet, placeholder::Placeholder,
+    prelude::*,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(PostUpdate, update_validity.before(PasswordSet))
+        .register_type::<InputValidity>();
+}
+
+/// Restricts the text of a widget
+#[derive(Component, Clone)]
+#[require(InputValidity)]
+pub enum InputFilter {
+    /// Digits with an optional leading `-`
+    Integer,
+    /// Digits with an optional leading `-` and a single `.`
+    Decimal,
+    /// Hexadecimal digits, upper or lower case
+    Hex,
+    /// Letters and digits in any script
+    Alphanumeric,
+    /// The whole text has to match. The pattern also has to match text that is only
+    /// partially typed, e.g. `^[A-Z]{0,3}[0-9]{0,4}$` rather than `^[A-Z]{3}[0-9]{4}$`
+    Regex(regex::Regex),
+    /// The whole text has to be accepted by the closure
+    Custom(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl std::fmt::Debug for InputFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Integer => write!(f, "Integer"),
+            Self::Decimal => write!(f, "Decimal"),
+            Self::Hex => write!(f, "Hex"),
+            Self::Alphanumeric => write!(f, "Alphanumeric"),
+            Self::Regex(regex) => f.debug_tuple("Regex").field(regex).finish(),
+            Self::Custom(_) => f.debug_tuple("Custom").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl InputFilter {
+    /// See [`InputFilter::Regex`]
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        regex::Regex::new(pattern).map(Self::Regex)
+    }
+
+    /// See [`InputFilter::Custom`]
+    pub fn custom(accepts: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(accepts))
+    }
+
+    /// Whether `text` is allowed in the widget, even if it isn't finished yet
+    pub fn accepts(&self, text: &str) -> bool {
+        match self {
+            Self::Integer => unsigned(text).chars().all(|c| c.is_ascii_digit()),
+            Self::Decimal => {
+                let number = unsigned(text);
+                number.chars().all(|c| c.is_ascii_digit() || c == '.')
+                    && number.matches('.').count() <= 1
+            }
+            Self::Hex => text.chars().all(|c| c.is_ascii_hexdigit()),
+            Self::Alphanumeric => text.chars().all(char::is_alphanumeric),
+            Self::Regex(regex) => regex.is_match(text),
+            Self::Custom(accepts) => accepts(text),
+        }
+    }
+
+    /// Whether non-empty `text` is a complete value
+    pub fn is_valid(&self, text: &str) -> bool {
+        match self {
+            Self::Integer | Self::Decimal => {
+                self.accepts(text) && unsigned(text).chars().any(|c| c.is_ascii_digit())
+            }
+            _ => self.accepts(text),
+        }
+    }
+}
+
+fn unsigned(text: &str) -> &str {
+    text.strip_prefix('-').unwrap_or(text)
+}
+
+/// Sent to a widget when its [`InputFilter`] rejects some inserted text
+#[derive(EntityEvent, Debug, Clone)]
+pub struct InputRejected {
+    pub entity: Entity,
+    /// The characters that were left out
+    pub text: String,
+}
+
+/// Whether the text of a widget with an [`InputFilter`] or [`InputMask`] is a complete value.
+///
+/// For masked widgets the filter is checked against [`InputMask::raw`], and every slot
+/// of the mask has to be filled.
+///
+/// Only changes when the state does, so `Changed<InputValidity>` can be used to react to it
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum InputValidity {
+    #[default]
+    Empty,
+    Valid,
+    Invalid,
+}
+
+impl InputValidity {
+    pub fn is_valid(&self) -> bool {
+        *self == Self::Valid
+    }
+}
+
+fn update_validity(
+    mut widgets: ParamSet<(
+        TextChanges,
+        Query<
+            (
+                Entity,
+                EditorBuffer,
+                Option<&InputFilter>,
+                Option<&InputMask>,
+                &mut InputValidity,
+                Option<&Placeholder>,
+            ),
+            Or<(With<InputFilter>, With<InputMask>)>,
+        >,
+    )>,
+    // only re-check the text when something that affects the result changed
+    changed: Query<(), Or<(Changed<InputFilter>, Changed<InputMask>)>>,
+) {
+    let text_changes = widgets.p0().read();
+    for (entity, buffer, filter_opt, mask_opt, mut validity, placeholder_opt) in
+        widgets.p1().iter_mut()
+    {
+        if !text_changes.contains(&entity) && !changed.contains(entity) {
+            continue;
+        }
+        let mut text = buffer.get_text();
+        // the filter checks the raw value of masked widgets
+        if let Some(mask) = mask_opt {
+            text = mask.raw(&text);
+        }
+        let new_validity = if text.is_empty() || placeholder_opt.is_some_and(Placeholder::is_active)
+        {
+            InputValidity::Empty
+        } else if mask_opt.is_none_or(|mask| mask.is_complete(&text))
+            && filter_opt.is_none_or(|filter| filter.is_valid(&text))
+        {
+            InputValidity::Valid
+        } else {
+            InputValidity::Invalid
+        };
+        validity.set_if_neq(new_validity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::Edit;
+
+    use super::*;
+    use crate::change::{CosmicTextEdited, EditKind};
+    use crate::history::EditOrigin;
+
+    #[test]
+    fn numbers_accept_partial_input() {
+        assert!(InputFilter::Integer.accepts("-"));
+        assert!(!InputFilter::Integer.is_valid("-"));
+        assert!(InputFilter::Integer.is_valid("-12"));
+        assert!(!InputFilter::Integer.accepts("1-2"));
+
+        assert!(InputFilter::Decimal.accepts("-0."));
+        assert!(InputFilter::Decimal.is_valid("-0."));
+        assert!(!InputFilter::Decimal.accepts("1.2.3"));
+
+        assert!(InputFilter::Hex.accepts("c0FFee"));
+        assert!(!InputFilter::Hex.accepts("0x"));
+    }
+
+    #[test]
+    fn validity_follows_text_edits() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_message::<CosmicTextEdited>()
+            .add_plugins(plugin);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, cosmic_text::Metrics::new(20., 20.))
+            .with_text(&mut font_system, "12", cosmic_text::Attrs::new());
+        let editor = CosmicEditor::clone_from_buffer(&buffer);
+        let widget = app
+            .world_mut()
+            .spawn((buffer, editor, InputFilter::Integer))
+            .id();
+        let validity = |app: &App| *app.world().get::<InputValidity>(widget).unwrap();
+
+        app.update();
+        assert_eq!(validity(&app), InputValidity::Valid);
+
+        // the editor changes every frame while the cursor blinks, that isn't an edit
+        app.world_mut()
+            .get_mut::<CosmicEditor>(widget)
+            .unwrap()
+            .with_buffer_mut(|buffer| {
+                buffer.set_text(
+                    &mut font_system,
+                    "1a",
+                    &cosmic_text::Attrs::new(),
+                    cosmic_text::Shaping::Advanced,
+                    None,
+                )
+            });
+        app.update();
+        assert_eq!(validity(&app), InputValidity::Valid);
+
+        app.world_mut().write_message(CosmicTextEdited {
+            entity: widget,
+            kind: EditKind::Insert,
+            origin: EditOrigin::Typing,
+            start: cosmic_text::Cursor::new(0, 1),
+            end: cosmic_text::Cursor::new(0, 2),
+            inserted: "a".into(),
+            removed: String::new(),
+            text: None,
+        });
+        app.update();
+        assert_eq!(validity(&app), InputValidity::Invalid);
+    }
+}
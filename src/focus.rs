@@ -1,9 +1,46 @@
 //! Manages the [`FocusedWidget`] resource
 //!
 //! Makes sure that the focused widget has a [`CosmicEditor`] component
-//! if its focused
+//! if its focused.
+//!
+//! Before focus moves, a [`FocusChangeRequest`] is triggered which observers can veto.
+//! Afterwards the widgets get [`FocusLost`] and [`FocusGained`]. Where the cursor goes
+//! when a widget gains focus is decided by its [`FocusCursor`], and whether Escape or
+//! clicking elsewhere takes the focus away by its [`FocusPolicy`].
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::focus::{FocusChangeRequest, FocusCursor, FocusGained, RequestFocus};
+//!
+//! fn setup(mut commands: Commands) {
+//!     let name = commands
+//!         .spawn((TextEdit, CosmicEditBuffer::default(), FocusCursor::SelectAll))
+//!         .observe(|gained: On<FocusGained>| info!("Editing {}", gained.entity))
+//!         .id();
+//!     commands.trigger(RequestFocus::new(name));
+//!
+//!     // keep the focus on the name until it is filled in
+//!     commands.add_observer(
+//!         move |mut request: On<FocusChangeRequest>, mut buffers: Query<EditorBuffer>| {
+//!             let empty = buffers
+//!                 .get_mut(name)
+//!                 .is_ok_and(|buffer| buffer.lines.iter().all(|line| line.text().is_empty()));
+//!             if request.from == Some(name) && empty {
+//!                 request.event_mut().veto();
+//!             }
+//!         },
+//!     );
+//! }
+//! ```
 
-use crate::prelude::*;
+use cosmic_text::{Cursor, Edit, Selection};
+
+use crate::{
+    buffer::{clamp_cursor, clamp_selection, SavedCursor},
+    prelude::*,
+    user_select::UserSelectNone,
+};
 
 /// System set for focus systems. Runs in `PostUpdate`
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
@@ -12,61 +49,361 @@ pub(crate) struct FocusSet;
 pub(crate) fn plugin(app: &mut App) {
     app.add_systems(
         PostUpdate,
-        (drop_editor_unfocused, add_editor_to_focused)
+        (
+            resolve_focus_change,
+            drop_editor_unfocused,
+            add_editor_to_focused,
+            send_focus_events,
+        )
             .chain()
             .in_set(FocusSet),
     )
+    .add_observer(request_focus)
+    .add_observer(blur_on_click_outside)
     .init_resource::<FocusedWidget>()
-    .register_type::<FocusedWidget>();
+    .init_resource::<FocusState>()
+    .init_resource::<FocusPolicy>()
+    .register_type::<FocusedWidget>()
+    .register_type::<FocusCursor>()
+    .register_type::<FocusPolicy>();
 }
 
 /// Resource struct that keeps track of the currently active editor entity.
 ///
 /// The focussed entity must have a [`CosmicEditBuffer`], and should have a
 /// [`CosmicEditor`] component as well if it can be mutated (i.e. isn't [`Readonly`]).
+///
+/// [`Disabled`] widgets can't be focused, and neither can widgets that are both
+/// [`ReadOnly`] and [`UserSelectNone`], as there is nothing to do with them.
 #[derive(Resource, Reflect, Default, Deref, DerefMut)]
 #[reflect(Resource)]
 pub struct FocusedWidget(pub Option<Entity>);
 
-/// Adds [`CosmicEditor`] by copying from existing [`CosmicEditBuffer`].
+impl FocusedWidget {
+    pub fn get(&self) -> Option<Entity> {
+        self.0
+    }
+
+    pub fn is_focused(&self, entity: Entity) -> bool {
+        self.0 == Some(entity)
+    }
+
+    /// Focuses `entity` using its [`FocusCursor`], see [`RequestFocus`] to pick one
+    pub fn focus(&mut self, entity: Entity) {
+        self.0 = Some(entity);
+    }
+
+    pub fn unfocus(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// Where the cursor goes when a widget gains focus
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum FocusCursor {
+    /// Back where it was when the widget lost focus
+    #[default]
+    Keep,
+    /// At the end of the text
+    End,
+    /// Selects all text, or [`FocusCursor::End`] for [`UserSelectNone`] widgets
+    SelectAll,
+}
+
+/// When a focused widget gives up its focus by itself.
+///
+/// As a resource this applies to all widgets, as a component to that widget only.
+/// Setting both fields to `false` keeps the focus until it is moved in code
+#[derive(Resource, Component, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource, Component)]
+pub struct FocusPolicy {
+    /// Escape unfocuses the widget, instead of only clearing the selection
+    pub blur_on_escape: bool,
+    /// Clicking anything that isn't a widget unfocuses it, including empty space
+    pub blur_on_click_outside: bool,
+}
+
+impl FocusPolicy {
+    /// Never unfocus by itself
+    pub const KEEP: Self = Self {
+        blur_on_escape: false,
+        blur_on_click_outside: false,
+    };
+
+    /// Unfocus on Escape or when clicking elsewhere
+    pub const BLUR: Self = Self {
+        blur_on_escape: true,
+        blur_on_click_outside: true,
+    };
+}
+
+/// Only Escape unfocuses by default
+impl Default for FocusPolicy {
+    fn default() -> Self {
+        Self {
+            blur_on_escape: true,
+            blur_on_click_outside: false,
+        }
+    }
+}
+
+/// Focuses a widget, optionally overriding its [`FocusCursor`]
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct RequestFocus {
+    pub entity: Entity,
+    pub cursor: Option<FocusCursor>,
+}
+
+impl RequestFocus {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            cursor: None,
+        }
+    }
+
+    pub fn with_cursor(mut self, cursor: FocusCursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+}
+
+/// Triggered before [`FocusedWidget`] changes. Observers can call
+/// [`FocusChangeRequest::veto`] to keep the focus where it was
+#[derive(Event, Debug)]
+pub struct FocusChangeRequest {
+    pub from: Option<Entity>,
+    pub to: Option<Entity>,
+    vetoed: bool,
+}
+
+impl FocusChangeRequest {
+    pub fn veto(&mut self) {
+        self.vetoed = true;
+    }
+
+    pub fn is_vetoed(&self) -> bool {
+        self.vetoed
+    }
+}
+
+/// Triggered on a widget after it gains focus, once it has its [`CosmicEditor`]
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct FocusGained {
+    pub entity: Entity,
+}
+
+/// Triggered on a widget after it loses focus
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct FocusLost {
+    pub entity: Entity,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct FocusState {
+    /// The focus that was last accepted
+    current: Option<Entity>,
+    lost: Option<Entity>,
+    gained: Option<Entity>,
+    /// Set by [`RequestFocus`]
+    cursor_override: Option<(Entity, FocusCursor)>,
+}
+
+fn request_focus(
+    request: On<RequestFocus>,
+    mut focused_widget: ResMut<FocusedWidget>,
+    mut state: ResMut<FocusState>,
+) {
+    focused_widget.focus(request.entity);
+    state.cursor_override = request.cursor.map(|cursor| (request.entity, cursor));
+}
+
+fn blur_on_click_outside(
+    click: On<Pointer<Click>>,
+    mut focused_widget: ResMut<FocusedWidget>,
+    policies: Query<&FocusPolicy>,
+    policy: Res<FocusPolicy>,
+    widgets: Query<(), With<CosmicEditBuffer>>,
+    parents: Query<&ChildOf>,
+) {
+    let target = click.original_event_target();
+    // only once, not for every entity the click bubbles up to
+    if click.entity != target || widgets.contains(target) {
+        return;
+    }
+    let Some(focused) = focused_widget.0 else {
+        return;
+    };
+    let policy = policies.get(focused).unwrap_or(&policy);
+    let inside = parents
+        .iter_ancestors(target)
+        .any(|parent| parent == focused);
+    if policy.blur_on_click_outside && !inside {
+        focused_widget.unfocus();
+    }
+}
+
+fn is_focusable(world: &World, entity: Entity) -> bool {
+    world.get::<CosmicEditBuffer>(entity).is_some()
+        && world.get::<Disabled>(entity).is_none()
+        && !(world.get::<ReadOnly>(entity).is_some()
+            && world.get::<UserSelectNone>(entity).is_some())
+}
+
+/// Accepts or reverts changes to [`FocusedWidget`]
+fn resolve_focus_change(world: &mut World) {
+    let to = world.resource::<FocusedWidget>().0;
+    let from = world.resource::<FocusState>().current;
+    if to == from {
+        return;
+    }
+    let from = from.filter(|from| world.get::<CosmicEditBuffer>(*from).is_some());
+
+    let mut request = FocusChangeRequest {
+        from,
+        to,
+        vetoed: false,
+    };
+    if to.is_some_and(|to| !is_focusable(world, to)) {
+        debug!(?to, "Widget can't be focused");
+        request.veto();
+    } else {
+        world.trigger_ref(&mut request);
+    }
+
+    let mut state = world.resource_mut::<FocusState>();
+    if request.vetoed {
+        state.cursor_override = None;
+        state.current = from;
+        world.resource_mut::<FocusedWidget>().0 = from;
+        return;
+    }
+    state.current = to;
+    state.lost = from;
+    state.gained = to;
+}
+
+fn send_focus_events(mut commands: Commands, mut state: ResMut<FocusState>) {
+    if let Some(entity) = state.lost.take() {
+        commands.trigger(FocusLost { entity });
+    }
+    if let Some(entity) = state.gained.take() {
+        commands.trigger(FocusGained { entity });
+    }
+}
+
+/// Adds [`CosmicEditor`] by copying from existing [`CosmicEditBuffer`],
+/// and places its cursor according to [`FocusCursor`].
 pub(crate) fn add_editor_to_focused(
     mut commands: Commands,
     active_editor: Res<FocusedWidget>,
-    q: Query<&CosmicEditBuffer, Without<CosmicEditor>>,
+    mut state: ResMut<FocusState>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut q: Query<(
+        &CosmicEditBuffer,
+        &SavedCursor,
+        Option<&mut CosmicEditor>,
+        Option<&FocusCursor>,
+        Has<UserSelectNone>,
+    )>,
 ) {
-    if let Some(e) = active_editor.0 {
-        let Ok(buffer) = q.get(e) else {
-            return;
-        };
-        let editor = CosmicEditor::clone_from_buffer(buffer);
-        trace!("Adding editor to focused widget");
-        commands.entity(e).insert(editor);
+    let Some(e) = active_editor.0 else {
+        return;
+    };
+    let cursor_override = state
+        .cursor_override
+        .take()
+        .filter(|(target, _)| *target == e)
+        .map(|(_, cursor)| cursor);
+    let Ok((buffer, saved, editor_opt, focus_cursor_opt, user_select_none)) = q.get_mut(e) else {
+        return;
+    };
+
+    match editor_opt {
+        // already focused
+        Some(mut editor) => {
+            if let Some(focus_cursor) = cursor_override {
+                place_cursor(&mut editor.editor, focus_cursor, saved, user_select_none);
+            }
+        }
+        None => {
+            let mut editor = CosmicEditor::clone_from_buffer(buffer);
+            let focus_cursor = cursor_override
+                .or(focus_cursor_opt.copied())
+                .unwrap_or_default();
+            let scroll = editor.with_buffer(|buffer| buffer.scroll());
+            place_cursor(&mut editor.editor, focus_cursor, saved, user_select_none);
+            if focus_cursor == FocusCursor::Keep {
+                // moving the cursor scrolls it into view, but the view should stay as it was
+                editor.shape_as_needed(&mut font_system.0, false);
+                editor.with_buffer_mut(|buffer| buffer.set_scroll(scroll));
+            }
+            trace!("Adding editor to focused widget");
+            commands.entity(e).insert(editor);
+        }
     }
 }
 
-/// Removes [`CosmicEditor`]
+fn place_cursor<'b>(
+    editor: &mut impl Edit<'b>,
+    focus_cursor: FocusCursor,
+    saved: &SavedCursor,
+    user_select_none: bool,
+) {
+    let (cursor, selection, end) = editor.with_buffer(|buffer| {
+        let last_line = buffer.lines.len().saturating_sub(1);
+        let end_index = buffer
+            .lines
+            .get(last_line)
+            .map_or(0, |line| line.text().len());
+        (
+            clamp_cursor(buffer, saved.cursor),
+            clamp_selection(buffer, saved.selection),
+            Cursor::new(last_line, end_index),
+        )
+    });
+
+    match focus_cursor {
+        FocusCursor::SelectAll if !user_select_none => {
+            editor.set_cursor(end);
+            editor.set_selection(Selection::Normal(Cursor::new(0, 0)));
+        }
+        FocusCursor::SelectAll | FocusCursor::End => {
+            editor.set_cursor(end);
+            editor.set_selection(Selection::None);
+        }
+        FocusCursor::Keep => {
+            editor.set_cursor(cursor);
+            editor.set_selection(if user_select_none {
+                Selection::None
+            } else {
+                selection
+            });
+        }
+    }
+}
+
+/// Removes [`CosmicEditor`], remembering its cursor in [`SavedCursor`]
 pub(crate) fn drop_editor_unfocused(
     mut commands: Commands,
     active_editor: Res<FocusedWidget>,
-    mut q: Query<(Entity, &mut CosmicEditBuffer, &CosmicEditor)>,
+    mut q: Query<(
+        Entity,
+        &mut CosmicEditBuffer,
+        &CosmicEditor,
+        &mut SavedCursor,
+    )>,
 ) {
-    match active_editor.0 {
-        None => {
-            for (e, mut buffer, editor) in q.iter_mut() {
-                *buffer = CosmicEditBuffer::from_downgrading_editor(editor);
-                trace!("Removing editor from all entities as there is no focussed widget",);
-                commands.entity(e).remove::<CosmicEditor>();
-            }
-        }
-        Some(focused) => {
-            for (e, mut b, editor) in q.iter_mut() {
-                if e != focused {
-                    *b = CosmicEditBuffer::from_downgrading_editor(editor);
-                    trace!("Removing editor from entity as its not focussed anymore",);
-                    commands.entity(e).remove::<CosmicEditor>();
-                }
-            }
+    for (e, mut buffer, editor, mut saved) in q.iter_mut() {
+        if active_editor.0 == Some(e) {
+            continue;
         }
+        *buffer = CosmicEditBuffer::from_downgrading_editor(editor);
+        saved.cursor = editor.cursor();
+        saved.selection = editor.selection();
+        trace!("Removing editor from entity as its not focussed anymore");
+        commands.entity(e).remove::<CosmicEditor>();
     }
 }
 
@@ -83,3 +420,156 @@ pub(crate) fn remove_focus_from_entity(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{Attrs, Metrics, Scroll};
+
+    use super::*;
+
+    fn test_app() -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>().add_plugins(plugin);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let mut spawn =
+            |text| {
+                let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.))
+                    .with_text(&mut font_system, text, Attrs::new());
+                app.world_mut().spawn(buffer).id()
+            };
+        let (a, b) = (spawn("first"), spawn("second"));
+        app.insert_resource(CosmicFontSystem(font_system));
+
+        (app, a, b)
+    }
+
+    fn cursor(app: &App, entity: Entity) -> (usize, Selection) {
+        let editor = app.world().get::<CosmicEditor>(entity).unwrap();
+        (editor.cursor().index, editor.selection())
+    }
+
+    #[test]
+    fn cursor_policy() {
+        let (mut app, a, _) = test_app();
+        app.world_mut()
+            .trigger(RequestFocus::new(a).with_cursor(FocusCursor::SelectAll));
+        app.update();
+        assert_eq!(cursor(&app, a), (5, Selection::Normal(Cursor::new(0, 0))));
+
+        app.world_mut()
+            .get_mut::<CosmicEditor>(a)
+            .unwrap()
+            .set_cursor(Cursor::new(0, 2));
+        app.world_mut().resource_mut::<FocusedWidget>().unfocus();
+        app.update();
+        app.world_mut().resource_mut::<FocusedWidget>().focus(a);
+        app.update();
+        assert_eq!(cursor(&app, a), (2, Selection::Normal(Cursor::new(0, 0))));
+    }
+
+    #[test]
+    fn keeps_scroll() {
+        let (mut app, a, _) = test_app();
+        app.world_mut().resource_mut::<FocusedWidget>().focus(a);
+        app.update();
+        app.world_mut()
+            .resource_scope(|world, mut font_system: Mut<CosmicFontSystem>| {
+                let mut editor = world.get_mut::<CosmicEditor>(a).unwrap();
+                editor.insert_string("\n\n\n", None);
+                editor.set_cursor(Cursor::new(0, 0));
+                editor.shape_as_needed(&mut font_system.0, false);
+                editor.with_buffer_mut(|buffer| buffer.set_scroll(Scroll::new(2, 0., 0.)));
+            });
+
+        app.world_mut().resource_mut::<FocusedWidget>().unfocus();
+        app.update();
+        app.world_mut().resource_mut::<FocusedWidget>().focus(a);
+        app.update();
+        let editor = app.world().get::<CosmicEditor>(a).unwrap();
+        assert_eq!(editor.with_buffer(|buffer| buffer.scroll().line), 2);
+    }
+
+    #[test]
+    fn click_outside_policy() {
+        use bevy::{
+            camera::NormalizedRenderTarget,
+            picking::{
+                backend::HitData,
+                pointer::{Location, PointerButton, PointerId},
+            },
+        };
+
+        let (mut app, a, b) = test_app();
+        let background = app.world_mut().spawn_empty().id();
+        app.world_mut().entity_mut(b).insert(FocusPolicy::BLUR);
+        let click = |app: &mut App, entity| {
+            let location = Location {
+                target: NormalizedRenderTarget::None {
+                    width: 1,
+                    height: 1,
+                },
+                position: Vec2::ZERO,
+            };
+            let click = Click {
+                button: PointerButton::Primary,
+                hit: HitData::new(Entity::PLACEHOLDER, 0., None, None),
+                duration: default(),
+            };
+            app.world_mut()
+                .trigger(Pointer::new(PointerId::Mouse, location, click, entity));
+            app.update();
+            app.world().resource::<FocusedWidget>().0
+        };
+
+        app.world_mut().resource_mut::<FocusedWidget>().focus(a);
+        app.update();
+        assert_eq!(click(&mut app, background), Some(a));
+
+        app.world_mut().resource_mut::<FocusedWidget>().focus(b);
+        app.update();
+        assert_eq!(click(&mut app, background), None);
+    }
+
+    #[test]
+    fn disabled_widgets_lose_focus() {
+        let (mut app, a, b) = test_app();
+        app.world_mut().entity_mut(b).insert(Disabled);
+        app.world_mut().resource_mut::<FocusedWidget>().focus(b);
+        app.update();
+        assert_eq!(app.world().resource::<FocusedWidget>().0, None);
+
+        app.world_mut().resource_mut::<FocusedWidget>().focus(a);
+        app.update();
+        app.world_mut().entity_mut(a).insert(Disabled);
+        app.update();
+        assert_eq!(app.world().resource::<FocusedWidget>().0, None);
+        assert!(app.world().get::<CosmicEditor>(a).is_none());
+    }
+
+    #[test]
+    fn veto_keeps_focus() {
+        let (mut app, a, b) = test_app();
+        app.world_mut()
+            .entity_mut(b)
+            .observe(|lost: On<FocusLost>, mut commands: Commands| {
+                commands.entity(lost.entity).insert(ReadOnly);
+            });
+        app.world_mut()
+            .add_observer(move |mut request: On<FocusChangeRequest>| {
+                if request.from == Some(b) {
+                    request.event_mut().veto();
+                }
+            });
+
+        app.world_mut().resource_mut::<FocusedWidget>().focus(b);
+        app.update();
+        app.world_mut().resource_mut::<FocusedWidget>().focus(a);
+        app.update();
+
+        assert!(app.world().resource::<FocusedWidget>().is_focused(b));
+        assert!(app.world().get::<CosmicEditor>(b).is_some());
+        assert!(app.world().get::<CosmicEditor>(a).is_none());
+        assert!(app.world().get::<ReadOnly>(b).is_none());
+    }
+}
@@ -9,21 +9,28 @@ pub mod cursor_icon;
 pub mod cursor_visibility;
 pub mod drag;
 pub mod hover;
+pub mod ime;
 pub mod keyboard;
+pub mod keymap;
 pub mod scroll;
+pub mod submit;
 
 /// System set for mouse and keyboard input events. Runs in [`PreUpdate`] and [`Update`]
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct InputSet;
 
+#[allow(deprecated)]
 pub(crate) fn plugin(app: &mut App) {
-    app.add_systems(PreUpdate, scroll::scroll.in_set(InputSet))
+    app.add_plugins((keymap::plugin, submit::plugin))
+        .add_systems(PreUpdate, scroll::scroll.in_set(InputSet))
         .add_systems(
             Update,
             (
                 keyboard::kb_move_cursor,
+                keyboard::kb_undo_redo,
                 keyboard::kb_input_text,
                 clipboard::kb_clipboard,
+                ime::read_ime,
                 (
                     cursor_icon::update_cursor_icon,
                     cursor_visibility::update_cursor_visibility,
@@ -32,6 +39,15 @@ pub(crate) fn plugin(app: &mut App) {
                 .chain()
                 .in_set(InputSet),
         )
+        .add_systems(
+            Update,
+            (
+                ime::toggle_ime,
+                ime::update_ime_position.pipe(render_implementations::debug_error),
+            )
+                .chain()
+                .after(InputSet),
+        )
         .add_message::<hover::TextHoverIn>()
         .add_message::<hover::TextHoverOut>()
         .add_message::<CosmicTextChanged>()
@@ -47,12 +63,26 @@ pub(crate) fn plugin(app: &mut App) {
     }
 }
 
-/// Text change events
-///
-/// Sent when text is changed in a cosmic buffer
-/// Contains the entity on which the text was changed, and the new text as a [`String`]
-#[derive(Message, Reflect, Debug)]
-pub struct CosmicTextChanged(pub (Entity, String));
+#[allow(deprecated)]
+pub use text_changed::CosmicTextChanged;
+
+// its own module, so the derives don't warn about the deprecation
+#[allow(deprecated)]
+mod text_changed {
+    use bevy::prelude::*;
+
+    /// Text change events
+    ///
+    /// Sent when text is changed in a cosmic buffer
+    /// Contains the entity on which the text was changed, and the new text as a [`String`]
+    ///
+    /// Copies the whole text on every edit, so it is only sent for widgets with
+    /// [`ReportFullText`](crate::change::ReportFullText). See
+    /// [`CosmicTextEdited`](crate::change::CosmicTextEdited) for what changed instead
+    #[deprecated(note = "use `CosmicTextEdited`, with `ReportFullText` for the whole text")]
+    #[derive(Message, Reflect, Debug)]
+    pub struct CosmicTextChanged(pub (Entity, String));
+}
 
 /// First variant is least important, last is most important
 #[derive(Component, Default, Debug)]
@@ -113,7 +143,11 @@ pub mod cancel {
     pub(super) fn handle_cancel(
         event: On<Pointer<Cancel>>,
         mut editor: Query<&mut InputState, With<CosmicEditBuffer>>,
+        disabled: Query<(), With<Disabled>>,
     ) {
+        if disabled.contains(event.entity) {
+            return;
+        }
         let Ok(mut input_state) = editor.get_mut(event.entity) else {
             warn_no_editor_on_picking_event("handling cursor `Cancel` event");
             return;
@@ -60,7 +60,11 @@ pub(super) fn handle_focused_click(
     mut font_system: ResMut<CosmicFontSystem>,
     buttons: Res<ButtonInput<KeyCode>>,
     mut click_state: ClickState,
+    disabled: Query<(), With<Disabled>>,
 ) -> render_implementations::Result<()> {
+    if disabled.contains(event.entity) {
+        return Ok(());
+    }
     let font_system = &mut font_system.0;
     let target = event.entity;
     let click = event.event();
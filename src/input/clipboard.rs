@@ -1,8 +1,17 @@
-use crate::{input::CosmicTextChanged, prelude::*, MaxChars, MaxLines};
+use crate::{
+    change::TextEditWriter,
+    filter::InputRejected,
+    history::{EditHistory, EditOrigin},
+    input::{
+        keyboard::{delete_limited, insert_text_limited, InsertLimits},
+        keymap::{CosmicKeymap, KeyCommand},
+    },
+    prelude::*,
+};
 
 #[cfg(target_arch = "wasm32")]
 use bevy::tasks::AsyncComputeTaskPool;
-use cosmic_text::{Action, Edit};
+use cosmic_text::Edit;
 #[cfg(target_arch = "wasm32")]
 #[allow(unused_imports)]
 use js_sys::Promise;
@@ -30,14 +39,17 @@ pub(crate) struct WasmPasteAsyncChannel {
 pub(crate) fn kb_clipboard(
     active_editor: Res<FocusedWidget>,
     keys: Res<ButtonInput<KeyCode>>,
-    mut evw_changed: MessageWriter<CosmicTextChanged>,
+    keymap: Res<CosmicKeymap>,
+    #[allow(unused_mut)] mut commands: Commands,
+    mut edits: TextEditWriter,
     #[allow(unused_variables, unused_mut)] mut font_system: ResMut<CosmicFontSystem>,
     mut cosmic_edit_query: Query<(
         &mut CosmicEditor,
-        &MaxLines,
-        &MaxChars,
+        &mut EditHistory,
+        InsertLimits,
         Entity,
         Option<&ReadOnly>,
+        Option<&CosmicKeymap>,
     )>,
     _channel: Option<Res<WasmPasteAsyncChannel>>,
 ) {
@@ -45,92 +57,115 @@ pub(crate) fn kb_clipboard(
         return;
     };
 
-    #[allow(unused_variables)]
-    if let Ok((mut editor, max_lines, max_chars, entity, readonly_opt)) =
+    #[allow(unused_variables, unused_mut)]
+    if let Ok((mut editor, mut history, limits, entity, readonly_opt, keymap_opt)) =
         cosmic_edit_query.get_mut(active_editor_entity)
     {
-        let command = crate::input::keyboard::keypress_command(&keys);
+        let command = keymap_opt
+            .unwrap_or(&keymap)
+            .just_pressed(&keys)
+            .map(|(command, _)| command);
 
         let readonly = readonly_opt.is_some();
 
-        let mut is_clipboard = false;
+        #[allow(unused_mut)]
+        let mut origin = EditOrigin::Paste;
+        history.begin(&mut editor.editor);
         #[cfg(not(target_arch = "wasm32"))]
         {
             if let Ok(mut clipboard) = arboard::Clipboard::new() {
-                if command && keys.just_pressed(KeyCode::KeyC) {
+                if command == Some(KeyCommand::Copy) {
                     if let Some(text) = editor.copy_selection() {
                         clipboard.set_text(text).unwrap();
                         return;
                     }
                 }
-                if command && keys.just_pressed(KeyCode::KeyX) && !readonly {
+                if command == Some(KeyCommand::Cut) && !readonly {
                     if let Some(text) = editor.copy_selection() {
                         clipboard.set_text(text).unwrap();
-                        editor.delete_selection();
+                        delete_limited(&mut editor.editor, &mut font_system.0, false, &limits);
                     }
-                    is_clipboard = true;
+                    origin = EditOrigin::Cut;
                 }
-                if command && keys.just_pressed(KeyCode::KeyV) && !readonly {
+                if command == Some(KeyCommand::Paste) && !readonly {
                     if let Ok(text) = clipboard.get_text() {
-                        for c in text.chars() {
-                            if max_chars.0 == 0 || editor.get_text().len() < max_chars.0 {
-                                if c == 0xA as char {
-                                    if max_lines.0 == 0
-                                        || editor.with_buffer(|b| b.lines.len()) < max_lines.0
-                                    {
-                                        editor.action(&mut font_system.0, Action::Insert(c));
-                                    }
-                                } else {
-                                    editor.action(&mut font_system.0, Action::Insert(c));
-                                }
-                            }
+                        let rejected = insert_text_limited(
+                            &mut editor.editor,
+                            &mut font_system.0,
+                            &text,
+                            &limits,
+                        );
+                        if !rejected.is_empty() {
+                            commands.trigger(InputRejected {
+                                entity,
+                                text: rejected,
+                            });
                         }
                     }
-                    is_clipboard = true;
                 }
             }
         }
 
         #[cfg(target_arch = "wasm32")]
         {
-            if command && keys.just_pressed(KeyCode::KeyC) {
+            if command == Some(KeyCommand::Copy) {
                 if let Some(text) = editor.copy_selection() {
                     write_clipboard_wasm(text.as_str());
                     return;
                 }
             }
 
-            if command && keys.just_pressed(KeyCode::KeyX) && !readonly {
+            if command == Some(KeyCommand::Cut) && !readonly {
                 if let Some(text) = editor.copy_selection() {
                     write_clipboard_wasm(text.as_str());
-                    editor.delete_selection();
+                    delete_limited(&mut editor.editor, &mut font_system.0, false, &limits);
                 }
-                is_clipboard = true;
+                origin = EditOrigin::Cut;
             }
-            if command && keys.just_pressed(KeyCode::KeyV) && !readonly {
-                let tx = _channel.unwrap().tx.clone();
-                let _task = AsyncComputeTaskPool::get().spawn(async move {
-                    let promise = read_clipboard_wasm();
-
-                    let result = JsFuture::from(promise).await;
-
-                    if let Ok(js_text) = result {
-                        if let Some(text) = js_text.as_string() {
-                            let _ = tx.try_send(WasmPaste { text, entity });
-                        }
-                    }
-                });
-
+            if command == Some(KeyCommand::Paste) && !readonly {
+                request_wasm_paste(&_channel.unwrap(), entity);
                 return;
             }
         }
 
-        if !is_clipboard {
-            return;
+        if history.commit(&mut editor.editor, origin) {
+            edits.write(entity, &mut history, || editor.get_text());
         }
+    }
+}
 
-        evw_changed.write(CosmicTextChanged((entity, editor.get_text())));
+/// Puts `text` on the system clipboard
+pub(crate) fn write_clipboard(text: String) {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(mut clipboard) = arboard::Clipboard::new() {
+        let _ = clipboard.set_text(text);
     }
+
+    #[cfg(target_arch = "wasm32")]
+    write_clipboard_wasm(text.as_str());
+}
+
+/// Reads the system clipboard. Not available on wasm, see [`request_wasm_paste`]
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_clipboard() -> Option<String> {
+    arboard::Clipboard::new().ok()?.get_text().ok()
+}
+
+/// Pastes the clipboard into `entity` once the browser hands it over
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn request_wasm_paste(channel: &WasmPasteAsyncChannel, entity: Entity) {
+    let tx = channel.tx.clone();
+    let _task = AsyncComputeTaskPool::get().spawn(async move {
+        let promise = read_clipboard_wasm();
+
+        let result = JsFuture::from(promise).await;
+
+        if let Ok(js_text) = result {
+            if let Some(text) = js_text.as_string() {
+                let _ = tx.try_send(WasmPaste { text, entity });
+            }
+        }
+    });
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -150,31 +185,33 @@ pub fn read_clipboard_wasm() -> Promise {
 #[cfg(target_arch = "wasm32")]
 pub(crate) fn poll_wasm_paste(
     channel: Res<WasmPasteAsyncChannel>,
-    mut editor_q: Query<(&mut CosmicEditor, &MaxChars, &MaxChars), Without<ReadOnly>>,
-    mut evw_changed: MessageWriter<CosmicTextChanged>,
+    mut editor_q: Query<(EditorBuffer, &mut EditHistory, InsertLimits), Without<ReadOnly>>,
+    mut commands: Commands,
+    mut edits: TextEditWriter,
     mut font_system: ResMut<CosmicFontSystem>,
 ) {
     let inlet = channel.rx.try_recv();
     match inlet {
         Ok(inlet) => {
             let entity = inlet.entity;
-            if let Ok((mut editor, max_chars, max_lines)) = editor_q.get_mut(entity) {
-                let text = inlet.text;
-                for c in text.chars() {
-                    if max_chars.0 == 0 || editor.get_text().len() < max_chars.0 {
-                        if c == 0xA as char {
-                            if max_lines.0 == 0
-                                || editor.with_buffer(|b| b.lines.len()) < max_lines.0
-                            {
-                                editor.action(&mut font_system.0, Action::Insert(c));
-                            }
-                        } else {
-                            editor.action(&mut font_system.0, Action::Insert(c));
-                        }
-                    }
+            if let Ok((mut buffer, mut history, limits)) = editor_q.get_mut(entity) {
+                let (changed, rejected) = buffer.with_editor_mut(|editor| {
+                    history.begin(editor);
+                    let rejected =
+                        insert_text_limited(editor, &mut font_system.0, &inlet.text, &limits);
+                    (history.commit(editor, EditOrigin::Paste), rejected)
+                });
+
+                if !rejected.is_empty() {
+                    commands.trigger(InputRejected {
+                        entity,
+                        text: rejected,
+                    });
                 }
 
-                evw_changed.write(CosmicTextChanged((entity, editor.get_text())));
+                if changed {
+                    edits.write(entity, &mut history, || buffer.get_text());
+                }
             }
         }
         Err(_) => {}
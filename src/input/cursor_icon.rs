@@ -63,9 +63,13 @@ impl GlobalCursorState {
     }
 }
 
-/// Doesn't take into account [`crate::UserSelectNone`] or [`crate::ReadOnly`]
+/// Doesn't take into account [`crate::UserSelectNone`] or [`crate::ReadOnly`],
+/// [`Disabled`] widgets never change the cursor
 pub(super) fn update_cursor_icon(
-    editors: Query<(&InputState, &HoverCursor, Entity, Has<CosmicEditor>), With<CosmicEditBuffer>>,
+    editors: Query<
+        (&InputState, &HoverCursor, Entity, Has<CosmicEditor>),
+        (With<CosmicEditBuffer>, Without<Disabled>),
+    >,
     focused_widget: Res<FocusedWidget>,
     mut cursor_icon: CursorIconUpdate,
 ) {
@@ -6,7 +6,7 @@ use bevy::{ecs::system::SystemParam, window::PrimaryWindow};
 
 use crate::prelude::*;
 
-use crate::input::CosmicTextChanged;
+use crate::change::CosmicTextEdited;
 
 #[derive(SystemParam)]
 pub(crate) struct CursorVisibility<'w, 's> {
@@ -20,7 +20,7 @@ impl CursorVisibility<'_, '_> {
 }
 
 pub(super) fn update_cursor_visibility(
-    editors_text_changed: MessageReader<CosmicTextChanged>,
+    editors_text_changed: MessageReader<CosmicTextEdited>,
     mouse_moved: MessageReader<MouseMotion>,
     mouse_clicked: Res<ButtonInput<MouseButton>>,
     mut cursor_visibility: CursorVisibility,
@@ -62,7 +62,11 @@ pub(super) fn handle_dragstart(
     on: On<Pointer<DragStart>>,
     mut editor: Query<(&mut InputState, &mut CosmicEditor, RelativeQuery), With<CosmicEditBuffer>>,
     mut font_system: ResMut<CosmicFontSystem>,
+    disabled: Query<(), With<Disabled>>,
 ) -> render_implementations::Result<()> {
+    if disabled.contains(on.entity) {
+        return Ok(());
+    }
     let font_system = &mut font_system.0;
     let event = on.event();
     let Ok((mut input_state, mut editor, sprite_relative)) = editor.get_mut(on.entity) else {
@@ -97,7 +101,11 @@ pub(super) fn handle_drag_continue(
     on: On<Pointer<Drag>>,
     mut editor: Query<(&InputState, &mut CosmicEditor)>,
     mut font_system: ResMut<CosmicFontSystem>,
+    disabled: Query<(), With<Disabled>>,
 ) {
+    if disabled.contains(on.entity) {
+        return;
+    }
     let font_system = &mut font_system.0;
     let event = on.event();
     let entity = on.entity;
@@ -131,7 +139,11 @@ pub(super) fn handle_drag_continue(
 pub(super) fn handle_dragend(
     on: On<Pointer<DragEnd>>,
     mut editor: Query<&mut InputState, With<CosmicEditBuffer>>,
+    disabled: Query<(), With<Disabled>>,
 ) {
+    if disabled.contains(on.entity) {
+        return;
+    }
     let event = on.event();
     let entity = on.entity;
 
@@ -63,7 +63,11 @@ pub(super) fn handle_hover_start(
     event: On<Pointer<Over>>,
     mut editor: Query<&mut InputState, With<CosmicEditBuffer>>,
     mut hover_in_evw: MessageWriter<TextHoverIn>,
+    disabled: Query<(), With<Disabled>>,
 ) {
+    if disabled.contains(event.entity) {
+        return;
+    }
     let Ok(mut input_state) = editor.get_mut(event.entity) else {
         warn_no_editor_on_picking_event("handling cursor `Over` event");
         return;
@@ -79,7 +83,11 @@ pub(super) fn handle_hover_start(
 pub(super) fn handle_hover_continue(
     event: On<Pointer<Move>>,
     mut editor: Query<&mut InputState, With<CosmicEditBuffer>>,
+    disabled: Query<(), With<Disabled>>,
 ) {
+    if disabled.contains(event.entity) {
+        return;
+    }
     let Ok(mut input_state) = editor.get_mut(event.entity) else {
         warn_no_editor_on_picking_event("handling cursor `Move` event");
         return;
@@ -92,7 +100,11 @@ pub(super) fn handle_hover_end(
     event: On<Pointer<Out>>,
     mut editor: Query<&mut InputState, With<CosmicEditBuffer>>,
     mut hover_out_evw: MessageWriter<TextHoverOut>,
+    disabled: Query<(), With<Disabled>>,
 ) {
+    if disabled.contains(event.entity) {
+        return;
+    }
     let Ok(mut input_state) = editor.get_mut(event.entity) else {
         warn_no_editor_on_picking_event("handling cursor `Out` event");
         return;
@@ -0,0 +1,531 @@
+//! IME (input method editor) support, used to type languages like Chinese, Japanese and Korean.
+//!
+//! While an editable widget is focused, [`Window::ime_enabled`] is turned on for the
+//! primary window. Text the IME is still composing (the "preedit") is drawn inline at the
+//! cursor with an underline, but is only inserted into the buffer once the IME commits it.
+//! The candidate window is placed under the cursor using [`Window::ime_position`].
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bevy::window::{Ime, PrimaryWindow};
+use cosmic_text::{Cursor, Edit, LayoutRun, Shaping};
+
+use crate::{
+    change::TextEditWriter,
+    filter::InputRejected,
+    history::{EditHistory, EditOrigin},
+    input::keyboard::{insert_text_limited, InsertLimits},
+    password::Password,
+    prelude::*,
+    render::draw_underline,
+    render_implementations::{self, RelativeQuery, WidgetCameras},
+};
+
+/// Text currently being composed by the IME, see [`Ime::Preedit`].
+///
+/// Automatically added to every [`CosmicEditBuffer`]
+#[derive(Component, Default, Debug)]
+pub struct ImePreedit {
+    value: String,
+    /// Byte range of the IME's own cursor inside `value`
+    cursor: Option<(usize, usize)>,
+}
+
+impl ImePreedit {
+    /// The text being composed, empty if the IME isn't composing
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_composing(&self) -> bool {
+        !self.value.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.value.clear();
+        self.cursor = None;
+    }
+
+    /// Lays out the preedit at the cursor of `editor`, to be drawn over its text
+    pub(crate) fn overlay(
+        &self,
+        editor: &CosmicEditor,
+        font_system: &mut cosmic_text::FontSystem,
+    ) -> Option<PreeditOverlay> {
+        if !self.is_composing() {
+            return None;
+        }
+        let (x, y) = editor.cursor_position()?;
+        let cursor = editor.cursor();
+        let buffer = editor.with_buffer(|buffer| {
+            let mut preedit = Buffer::new(font_system, buffer.metrics());
+            // in the style of the text before the cursor
+            let attrs = buffer
+                .lines
+                .get(cursor.line)
+                .map_or_else(cosmic_text::Attrs::new, |line| {
+                    line.attrs_list().get_span(cursor.index.saturating_sub(1))
+                });
+            preedit.set_text(font_system, &self.value, &attrs, Shaping::Advanced, None);
+            preedit
+        });
+        let (width, line_height, ime_cursor) = buffer
+            .layout_runs()
+            .next()
+            .map(|run| {
+                let ime_cursor = self.cursor.map(|(begin, _)| {
+                    run.glyphs
+                        .iter()
+                        .find(|glyph| glyph.start >= begin)
+                        .map_or(run.line_w, |glyph| glyph.x) as i32
+                });
+                (run.line_w.ceil() as i32, run.line_height as i32, ime_cursor)
+            })
+            .unwrap_or_default();
+
+        let mut hasher = DefaultHasher::new();
+        (&self.value, self.cursor, x, y).hash(&mut hasher);
+        Some(PreeditOverlay {
+            buffer,
+            line: cursor.line,
+            origin: IVec2::new(x, y),
+            width,
+            line_height,
+            cursor: ime_cursor,
+            signature: hasher.finish(),
+        })
+    }
+}
+
+/// The preedit of a focused widget, laid out on its own and drawn over the text at the cursor.
+///
+/// It is never inserted into the buffer, so it can't end up in the text, the edit history
+/// or change detection. The text after the cursor moves aside to make room, see
+/// [`PreeditOverlay::make_room`]
+pub(crate) struct PreeditOverlay {
+    buffer: Buffer,
+    /// Buffer line of the cursor
+    line: usize,
+    /// Top left of the preedit, in buffer coordinates
+    origin: IVec2,
+    width: i32,
+    line_height: i32,
+    /// Position of the IME's own cursor, from `origin`
+    cursor: Option<i32>,
+    /// Hash of the preedit and where it is drawn
+    signature: u64,
+}
+
+impl PreeditOverlay {
+    /// The buffer line the preedit is drawn on
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn signature(&self) -> u64 {
+        self.signature
+    }
+
+    pub fn layout_runs(&self) -> impl Iterator<Item = LayoutRun<'_>> {
+        self.buffer.layout_runs()
+    }
+
+    /// Wraps `renderer` to move everything after the cursor on its visual line
+    /// to the right, by the width of the preedit
+    pub fn make_room<'a, R: cosmic_text::Renderer>(
+        &'a self,
+        renderer: &'a mut R,
+    ) -> MakeRoom<'a, R> {
+        MakeRoom {
+            overlay: self,
+            renderer,
+        }
+    }
+
+    /// Draws the preedit with an underline, and the IME's cursor if it has one
+    pub fn draw(
+        &self,
+        renderer: &mut impl cosmic_text::Renderer,
+        text_color: cosmic_text::Color,
+        cursor_color: cosmic_text::Color,
+    ) {
+        let origin = self.origin.as_vec2();
+        for run in self.buffer.layout_runs() {
+            for glyph in run.glyphs {
+                renderer.glyph(
+                    glyph.physical((origin.x, origin.y + run.line_y), 1.),
+                    glyph.color_opt.unwrap_or(text_color),
+                );
+            }
+        }
+        let end = self
+            .buffer
+            .lines
+            .first()
+            .map_or(0, |line| line.text().len());
+        draw_underline(
+            &self.buffer,
+            Cursor::new(0, 0),
+            Cursor::new(0, end),
+            text_color,
+            |x, y, w, h, color| {
+                renderer.rectangle(self.origin.x + x, self.origin.y + y, w, h, color);
+            },
+        );
+        if let Some(x) = self.cursor {
+            renderer.rectangle(
+                self.origin.x + x,
+                self.origin.y,
+                1,
+                self.line_height as u32,
+                cursor_color,
+            );
+        }
+    }
+}
+
+/// See [`PreeditOverlay::make_room`]
+pub(crate) struct MakeRoom<'a, R> {
+    overlay: &'a PreeditOverlay,
+    renderer: &'a mut R,
+}
+
+impl<R> MakeRoom<'_, R> {
+    fn shift(&self, x: i32, y: i32) -> i32 {
+        let PreeditOverlay {
+            origin,
+            width,
+            line_height,
+            ..
+        } = self.overlay;
+        if x >= origin.x && y >= origin.y && y < origin.y + line_height {
+            x + width
+        } else {
+            x
+        }
+    }
+}
+
+impl<R: cosmic_text::Renderer> cosmic_text::Renderer for MakeRoom<'_, R> {
+    fn rectangle(&mut self, x: i32, y: i32, w: u32, h: u32, color: cosmic_text::Color) {
+        let x = self.shift(x, y);
+        self.renderer.rectangle(x, y, w, h, color);
+    }
+
+    fn glyph(&mut self, mut physical_glyph: cosmic_text::PhysicalGlyph, color: cosmic_text::Color) {
+        physical_glyph.x = self.shift(physical_glyph.x, physical_glyph.y);
+        self.renderer.glyph(physical_glyph, color);
+    }
+}
+
+/// Turns IME on for the primary window when an editable widget gains focus,
+/// and off when it loses focus or stops being editable.
+///
+/// The window is left alone otherwise, so other code can use IME while no widget is focused
+pub(crate) fn toggle_ime(
+    focused_widget: Res<FocusedWidget>,
+    editable: Query<(), (With<CosmicEditBuffer>, Without<ReadOnly>, Without<Password>)>,
+    mut preedits: Query<(Entity, &mut ImePreedit)>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+    mut was_enabled: Local<bool>,
+) {
+    // checked every frame, so it follows the focused widget becoming read-only
+    let enabled = focused_widget.0.is_some_and(|e| editable.contains(e));
+    if enabled != *was_enabled {
+        *was_enabled = enabled;
+        if window.ime_enabled != enabled {
+            window.ime_enabled = enabled;
+        }
+    }
+
+    if !focused_widget.is_changed() {
+        return;
+    }
+
+    for (entity, mut preedit) in preedits.iter_mut() {
+        if preedit.is_composing() && focused_widget.0 != Some(entity) {
+            preedit.clear();
+        }
+    }
+}
+
+/// Stores preedit text and inserts committed text into the focused widget
+pub(crate) fn read_ime(
+    focused_widget: Res<FocusedWidget>,
+    mut ime_events: MessageReader<Ime>,
+    mut editors: Query<
+        (
+            &mut CosmicEditor,
+            &mut ImePreedit,
+            &mut EditHistory,
+            InsertLimits,
+        ),
+        Without<ReadOnly>,
+    >,
+    mut commands: Commands,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut edits: TextEditWriter,
+) {
+    for event in ime_events.read() {
+        let Some(active_editor_entity) = focused_widget.0 else {
+            continue;
+        };
+        let Ok((mut editor, mut preedit, mut history, limits)) =
+            editors.get_mut(active_editor_entity)
+        else {
+            continue;
+        };
+
+        match event {
+            Ime::Preedit { value, cursor, .. } => {
+                preedit.value.clone_from(value);
+                preedit.cursor = *cursor;
+                editor.set_redraw(true);
+            }
+            Ime::Commit { value, .. } => {
+                preedit.clear();
+
+                history.begin(&mut editor.editor);
+                let rejected =
+                    insert_text_limited(&mut editor.editor, &mut font_system.0, value, &limits);
+                if !rejected.is_empty() {
+                    commands.trigger(InputRejected {
+                        entity: active_editor_entity,
+                        text: rejected,
+                    });
+                }
+                if history.commit(&mut editor.editor, EditOrigin::Typing) {
+                    edits.write(active_editor_entity, &mut history, || editor.get_text());
+                }
+                editor.set_redraw(true);
+            }
+            Ime::Disabled { .. } => {
+                preedit.clear();
+                editor.set_redraw(true);
+            }
+            Ime::Enabled { .. } => {}
+        }
+    }
+}
+
+/// Moves the IME candidate window under the cursor of the focused widget
+pub(crate) fn update_ime_position(
+    focused_widget: Res<FocusedWidget>,
+    mut editors: Query<(&mut CosmicEditor, RelativeQuery)>,
+    widget_cameras: WidgetCameras,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+) -> render_implementations::Result<()> {
+    if !window.ime_enabled {
+        return Ok(());
+    }
+    let Some(active_editor_entity) = focused_widget.0 else {
+        return Ok(());
+    };
+    let Ok((mut editor, relative)) = editors.get_mut(active_editor_entity) else {
+        return Ok(());
+    };
+
+    let Some((x, y)) = editor.cursor_position() else {
+        return Ok(());
+    };
+    let line_height = editor.with_buffer(|buffer| buffer.metrics().line_height);
+    let buffer_size =
+        editor.with_buffer_mut(|buffer| buffer.borrow_with(&mut font_system.0).expected_size());
+    let camera = widget_cameras
+        .camera_of(active_editor_entity)
+        .and_then(|camera| cameras.get(camera).ok());
+
+    let position = relative.buffer_coord_to_window(
+        Vec2::new(x as f32, y as f32 + line_height),
+        buffer_size,
+        camera,
+    )?;
+    if window.ime_position != position {
+        window.ime_position = position;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::Metrics;
+
+    use super::*;
+    use crate::change::CosmicTextEdited;
+    #[allow(deprecated)]
+    use crate::input::CosmicTextChanged;
+
+    #[allow(deprecated)]
+    fn test_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_message::<Ime>()
+            .add_message::<CosmicTextChanged>()
+            .add_message::<CosmicTextEdited>()
+            .add_systems(Update, read_ime);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        let editor = CosmicEditor::clone_from_buffer(&buffer);
+        app.insert_resource(CosmicFontSystem(font_system));
+
+        let entity = app.world_mut().spawn((buffer, editor)).id();
+        app.insert_resource(FocusedWidget(Some(entity)));
+
+        (app, entity)
+    }
+
+    fn text(app: &App, entity: Entity) -> String {
+        app.world().get::<CosmicEditor>(entity).unwrap().get_text()
+    }
+
+    #[test]
+    fn preedit_is_only_inserted_on_commit() {
+        let (mut app, entity) = test_app();
+
+        app.world_mut().write_message(Ime::Preedit {
+            window: Entity::PLACEHOLDER,
+            value: "にほ".into(),
+            cursor: Some((6, 6)),
+        });
+        app.update();
+        assert!(app
+            .world()
+            .get::<ImePreedit>(entity)
+            .unwrap()
+            .is_composing());
+        assert_eq!(text(&app, entity), "");
+
+        app.world_mut().write_message(Ime::Commit {
+            window: Entity::PLACEHOLDER,
+            value: "日本".into(),
+        });
+        app.update();
+        assert!(!app
+            .world()
+            .get::<ImePreedit>(entity)
+            .unwrap()
+            .is_composing());
+        assert_eq!(text(&app, entity), "日本");
+        assert_eq!(
+            app.world()
+                .resource::<Messages<CosmicTextEdited>>()
+                .iter_current_update_messages()
+                .count(),
+            1
+        );
+    }
+
+    /// Records where glyphs and rectangles are drawn
+    #[derive(Default)]
+    struct Recorder {
+        glyphs: Vec<i32>,
+        rectangles: Vec<(i32, cosmic_text::Color)>,
+    }
+
+    impl cosmic_text::Renderer for Recorder {
+        fn rectangle(&mut self, x: i32, _: i32, _: u32, _: u32, color: cosmic_text::Color) {
+            self.rectangles.push((x, color));
+        }
+
+        fn glyph(&mut self, physical_glyph: cosmic_text::PhysicalGlyph, _: cosmic_text::Color) {
+            self.glyphs.push(physical_glyph.x);
+        }
+    }
+
+    #[test]
+    fn preedit_overlay_makes_room_without_touching_the_buffer() {
+        let mut db = cosmic_text::fontdb::Database::new();
+        db.load_font_data(include_bytes!("../font/FiraMono-Regular-subset.ttf").to_vec());
+        let mut font_system = cosmic_text::FontSystem::new_with_locale_and_db("en-US".into(), db);
+        let attrs = cosmic_text::Attrs::new().family(cosmic_text::Family::Name("Fira Mono"));
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.)).with_text(
+            &mut font_system,
+            "abc",
+            attrs,
+        );
+        let mut editor = CosmicEditor::clone_from_buffer(&buffer);
+        editor.set_cursor(Cursor::new(0, 1));
+        editor.borrow_with(&mut font_system).shape_as_needed(false);
+
+        let black = cosmic_text::Color::rgb(0, 0, 0);
+        let red = cosmic_text::Color::rgb(255, 0, 0);
+        let mut plain = Recorder::default();
+        editor.render(&mut plain, black, black, black, black);
+        let [a, b, c] = plain.glyphs[..] else {
+            panic!("expected three glyphs");
+        };
+
+        let preedit = ImePreedit {
+            value: "c".into(),
+            cursor: Some((1, 1)),
+        };
+        let overlay = preedit.overlay(&editor, &mut font_system).unwrap();
+        let hidden = cosmic_text::Color::rgba(0, 0, 0, 0);
+        let mut composed = Recorder::default();
+        editor.render(
+            &mut overlay.make_room(&mut composed),
+            black,
+            hidden,
+            hidden,
+            black,
+        );
+        overlay.draw(&mut composed, black, red);
+
+        // the preedit is drawn at the cursor, and the text after it moves aside
+        let width = c - b;
+        assert_eq!(composed.glyphs, [a, b + width, c + width, b]);
+        // with the IME's cursor after it
+        assert!(composed.rectangles.contains(&(c, red)));
+        assert_eq!(editor.get_text(), "abc");
+        assert_eq!(editor.cursor(), Cursor::new(0, 1));
+    }
+
+    #[test]
+    fn ime_follows_the_focused_widget() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_systems(Update, toggle_ime);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        let window = app
+            .world_mut()
+            .spawn((Window::default(), PrimaryWindow))
+            .id();
+        let widget = app.world_mut().spawn(buffer).id();
+        app.insert_resource(FocusedWidget(Some(widget)));
+        let ime_enabled = |app: &App| app.world().get::<Window>(window).unwrap().ime_enabled;
+
+        let window_changed = |app: &App| {
+            app.world()
+                .entity(window)
+                .get_ref::<Window>()
+                .unwrap()
+                .last_changed()
+        };
+
+        app.update();
+        assert!(ime_enabled(&app));
+        let changed = window_changed(&app);
+        app.update();
+        assert_eq!(window_changed(&app), changed);
+
+        app.world_mut().entity_mut(widget).insert(ReadOnly);
+        app.update();
+        assert!(!ime_enabled(&app));
+
+        // other code can use IME while no editable widget is focused
+        app.world_mut().insert_resource(FocusedWidget(None));
+        app.world_mut()
+            .get_mut::<Window>(window)
+            .unwrap()
+            .ime_enabled = true;
+        app.update();
+        app.update();
+        assert!(ime_enabled(&app));
+    }
+}
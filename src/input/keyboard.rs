@@ -1,7 +1,25 @@
-use bevy::input::keyboard::{Key, KeyboardInput};
-use cosmic_text::{Action, Cursor, Motion, Selection};
+use bevy::ecs::query::QueryData;
+use bevy::input::{
+    keyboard::{Key, KeyboardInput},
+    ButtonState,
+};
+use cosmic_text::{Action, Cursor, Edit, FontSystem, Motion, Selection};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{input::CosmicTextChanged, prelude::*, MaxChars, MaxLines};
+use crate::{
+    change::TextEditWriter,
+    filter::{InputFilter, InputRejected},
+    history::{EditHistory, EditOrigin},
+    input::{
+        ime::ImePreedit,
+        keymap::{CosmicKeymap, KeyChord, KeyCommand},
+        submit::{EnterBehavior, SubmitRequest},
+    },
+    mask::{self, InputMask},
+    placeholder::Placeholder,
+    prelude::*,
+    MaxChars, MaxLines,
+};
 
 pub(super) fn keypress_command(keys: &ButtonInput<KeyCode>) -> bool {
     #[cfg(target_os = "macos")]
@@ -27,148 +45,92 @@ pub(super) fn keypress_command(keys: &ButtonInput<KeyCode>) -> bool {
 }
 
 pub(crate) fn kb_move_cursor(
-    active_editor: Res<FocusedWidget>,
+    mut active_editor: ResMut<FocusedWidget>,
     keys: Res<ButtonInput<KeyCode>>,
-    mut cosmic_edit_query: Query<(&mut CosmicEditor,)>,
+    keymap: Res<CosmicKeymap>,
+    focus_policy: Res<FocusPolicy>,
+    mut cosmic_edit_query: Query<(
+        &mut CosmicEditor,
+        Option<&CosmicKeymap>,
+        Option<&ImePreedit>,
+        Option<&InputMask>,
+        Option<&FocusPolicy>,
+    )>,
     mut font_system: ResMut<CosmicFontSystem>,
 ) {
     let Some(active_editor_entity) = active_editor.0 else {
         return;
     };
-    if let Ok((mut editor,)) = cosmic_edit_query.get_mut(active_editor_entity) {
+    if let Ok((mut editor, keymap_opt, preedit_opt, mask_opt, focus_policy_opt)) =
+        cosmic_edit_query.get_mut(active_editor_entity)
+    {
+        // the IME handles navigation inside the text it is composing
+        if preedit_opt.is_some_and(ImePreedit::is_composing) {
+            return;
+        }
+
         if keys.get_just_pressed().len() != 0 {
             editor.cursor_visible = true;
             editor.cursor_timer.reset();
+            editor.set_redraw(true);
         }
 
-        let command = keypress_command(&keys);
-
-        #[cfg(target_arch = "wasm32")]
-        let command = if web_sys::window()
-            .unwrap()
-            .navigator()
-            .user_agent()
-            .unwrap_or("NoUA".into())
-            .contains("Macintosh")
-        {
-            keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight])
-        } else {
-            command
+        let keymap = keymap_opt.unwrap_or(&keymap);
+        let Some((command, extend_selection)) = keymap.just_pressed(&keys) else {
+            return;
         };
 
-        #[cfg(target_os = "macos")]
-        let option = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
-
-        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
-
-        // if shift key is pressed
-        let already_has_selection = editor.selection() != Selection::None;
-        if shift && !already_has_selection {
-            let cursor = editor.cursor();
-            editor.set_selection(Selection::Normal(cursor));
-        }
-
-        #[cfg(target_os = "macos")]
-        let should_jump = command && option;
-        #[cfg(not(target_os = "macos"))]
-        let should_jump = command;
-
-        if should_jump && keys.just_pressed(KeyCode::ArrowLeft) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::PreviousWord));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if should_jump && keys.just_pressed(KeyCode::ArrowRight) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::NextWord));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if should_jump && keys.just_pressed(KeyCode::Home) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::BufferStart));
-            if !shift {
-                editor.set_selection(Selection::None);
+        let motion = match command {
+            KeyCommand::Left => Motion::Left,
+            KeyCommand::Right => Motion::Right,
+            KeyCommand::Up => Motion::Up,
+            KeyCommand::Down => Motion::Down,
+            KeyCommand::PreviousWord => Motion::PreviousWord,
+            KeyCommand::NextWord => Motion::NextWord,
+            KeyCommand::Home => Motion::Home,
+            KeyCommand::End => Motion::End,
+            KeyCommand::BufferStart => Motion::BufferStart,
+            KeyCommand::BufferEnd => Motion::BufferEnd,
+            KeyCommand::PageUp => Motion::PageUp,
+            KeyCommand::PageDown => Motion::PageDown,
+            KeyCommand::SelectAll => {
+                editor.action(&mut font_system.0, Action::Motion(Motion::BufferEnd));
+                let current_cursor = editor.cursor();
+                editor.set_selection(Selection::Normal(Cursor {
+                    line: 0,
+                    index: 0,
+                    affinity: current_cursor.affinity,
+                }));
+                return;
             }
-            return;
-        }
-        if should_jump && keys.just_pressed(KeyCode::End) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::BufferEnd));
-            if !shift {
-                editor.set_selection(Selection::None);
+            KeyCommand::Escape => {
+                if focus_policy_opt.unwrap_or(&focus_policy).blur_on_escape {
+                    active_editor.unfocus();
+                } else {
+                    editor.action(&mut font_system.0, Action::Escape);
+                }
+                return;
             }
-            return;
-        }
+            _ => return,
+        };
 
-        if keys.just_pressed(KeyCode::ArrowLeft) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Left));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::ArrowRight) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Right));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::ArrowUp) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Up));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::ArrowDown) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Down));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::Escape) {
-            editor.action(&mut font_system.0, Action::Escape);
-        }
-        if command && keys.just_pressed(KeyCode::KeyA) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::BufferEnd));
-            let current_cursor = editor.cursor();
-            editor.set_selection(Selection::Normal(Cursor {
-                line: 0,
-                index: 0,
-                affinity: current_cursor.affinity,
-            }));
-            return;
-        }
-        if keys.just_pressed(KeyCode::Home) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Home));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
+        if extend_selection && editor.selection() == Selection::None {
+            let cursor = editor.cursor();
+            editor.set_selection(Selection::Normal(cursor));
         }
-        if keys.just_pressed(KeyCode::End) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::End));
-            if !shift {
-                editor.set_selection(Selection::None);
+        match (mask_opt, motion) {
+            // step over the literals of the mask
+            (Some(mask), Motion::Left | Motion::Right) => {
+                mask::step_cursor(mask, &mut editor.editor, motion == Motion::Right);
             }
-            return;
-        }
-        if keys.just_pressed(KeyCode::PageUp) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::PageUp));
-            if !shift {
-                editor.set_selection(Selection::None);
+            (Some(mask), _) => {
+                editor.action(&mut font_system.0, Action::Motion(motion));
+                mask::snap_cursor(mask, &mut editor.editor);
             }
-            return;
+            (None, _) => editor.action(&mut font_system.0, Action::Motion(motion)),
         }
-        if keys.just_pressed(KeyCode::PageDown) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::PageDown));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
+        if !extend_selection {
+            editor.set_selection(Selection::None);
         }
     }
 }
@@ -176,118 +138,561 @@ pub(crate) fn kb_move_cursor(
 pub(crate) fn kb_input_text(
     active_editor: Res<FocusedWidget>,
     keys: Res<ButtonInput<KeyCode>>,
+    keymap: Res<CosmicKeymap>,
     mut char_evr: MessageReader<KeyboardInput>,
     mut cosmic_edit_query: Query<(
         &mut CosmicEditor,
-        &MaxLines,
-        &MaxChars,
+        &mut EditHistory,
+        InsertLimits,
         Entity,
         Option<&ReadOnly>,
+        Option<&CosmicKeymap>,
+        Option<&ImePreedit>,
+        Option<&EnterBehavior>,
     )>,
-    mut evw_changed: MessageWriter<CosmicTextChanged>,
+    mut commands: Commands,
+    mut edits: TextEditWriter,
+    mut evw_submit: MessageWriter<SubmitRequest>,
     mut font_system: ResMut<CosmicFontSystem>,
-    mut is_deleting: Local<bool>,
+    mut text_input: Local<TextInputState>,
 ) {
     let Some(active_editor_entity) = active_editor.0 else {
         return;
     };
 
-    if let Ok((mut editor, max_lines, max_chars, entity, readonly_opt)) =
-        cosmic_edit_query.get_mut(active_editor_entity)
+    if let Ok((
+        mut editor,
+        mut history,
+        limits,
+        entity,
+        readonly_opt,
+        keymap_opt,
+        preedit_opt,
+        enter_opt,
+    )) = cosmic_edit_query.get_mut(active_editor_entity)
     {
         let command = keypress_command(&keys);
         if keys.get_just_pressed().len() != 0 {
             editor.cursor_visible = true;
             editor.cursor_timer.reset();
+            editor.set_redraw(true);
         }
-        let readonly = readonly_opt.is_some();
-
-        if keys.just_pressed(KeyCode::Backspace) & !readonly {
-            // fix for issue #8
-            let select = editor.selection();
-            match select {
-                Selection::Line(cursor) => {
-                    if editor.cursor().line == cursor.line && editor.cursor().index == cursor.index
-                    {
-                        editor.set_selection(Selection::None);
-                    }
+
+        if readonly_opt.is_some() {
+            return;
+        }
+
+        // key presses belong to the IME while it is composing, text arrives through `Ime::Commit`
+        if preedit_opt.is_some_and(ImePreedit::is_composing) {
+            char_evr.clear();
+            return;
+        }
+
+        let keymap = keymap_opt.unwrap_or(&keymap);
+
+        history.begin(&mut editor.editor);
+        let mut origin = EditOrigin::Typing;
+        let mut rejected = String::new();
+
+        for char_ev in char_evr.read() {
+            if char_ev.state != ButtonState::Pressed {
+                continue;
+            }
+
+            // deletions repeat while held, so they are handled here instead of `kb_move_cursor`
+            match keymap.resolve(KeyChord::from_input(char_ev.key_code, &keys)) {
+                Some((KeyCommand::DeleteBackward, _)) => {
+                    delete_limited(&mut editor.editor, &mut font_system.0, false, &limits);
+                    origin = EditOrigin::Deleting;
+                    continue;
                 }
-                Selection::Normal(cursor) => {
-                    if editor.cursor().line == cursor.line && editor.cursor().index == cursor.index
-                    {
-                        editor.set_selection(Selection::None);
-                    }
+                Some((KeyCommand::DeleteForward, _)) => {
+                    delete_limited(&mut editor.editor, &mut font_system.0, true, &limits);
+                    origin = EditOrigin::Deleting;
+                    continue;
                 }
-                Selection::Word(cursor) => {
-                    if editor.cursor().line == cursor.line && editor.cursor().index == cursor.index
-                    {
-                        editor.set_selection(Selection::None);
+                // handled by other systems, and shouldn't type anything
+                Some(_) => continue,
+                None => {}
+            }
+
+            match char_ev.key_code {
+                KeyCode::Enter | KeyCode::NumpadEnter => {
+                    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+                    let behavior = enter_opt.copied().unwrap_or_default();
+                    if behavior.submits(shift) || !newline_fits(&editor.editor, &limits) {
+                        evw_submit.write(SubmitRequest(entity));
+                    } else {
+                        // to have new line on wasm rather than E
+                        rejected += &insert_text_limited(
+                            &mut editor.editor,
+                            &mut font_system.0,
+                            "\n",
+                            &limits,
+                        );
                     }
+                    continue;
                 }
-                Selection::None => {}
+                _ => {}
             }
 
-            *is_deleting = true;
+            if command {
+                continue;
+            }
+
+            if let Some(text) = text_input.text_from(char_ev) {
+                rejected +=
+                    &insert_text_limited(&mut editor.editor, &mut font_system.0, &text, &limits);
+            }
         }
 
-        if keys.just_released(KeyCode::Backspace) {
-            *is_deleting = false;
+        if !rejected.is_empty() {
+            commands.trigger(InputRejected {
+                entity,
+                text: rejected,
+            });
         }
-        if keys.just_pressed(KeyCode::Delete) && !readonly {
-            editor.action(&mut font_system.0, Action::Delete);
-            editor.with_buffer_mut(|b| b.set_redraw(true));
+
+        if history.commit(&mut editor.editor, origin) {
+            edits.write(entity, &mut history, || editor.get_text());
         }
+    }
+}
 
-        if readonly {
-            return;
+/// Backspacing with a selection whose anchor is on the cursor
+/// would otherwise delete nothing
+fn clear_empty_selection<'b>(editor: &mut impl Edit<'b>) {
+    let cursor = editor.cursor();
+    match editor.selection() {
+        Selection::Line(anchor) | Selection::Normal(anchor) | Selection::Word(anchor) => {
+            if anchor.line == cursor.line && anchor.index == cursor.index {
+                editor.set_selection(Selection::None);
+            }
         }
+        Selection::None => {}
+    }
+}
 
-        let mut is_edit = false;
-        let mut is_return = false;
-        if keys.just_pressed(KeyCode::Enter) {
-            is_return = true;
-            if (max_lines.0 == 0 || editor.with_buffer(|b| b.lines.len()) < max_lines.0)
-                && (max_chars.0 == 0 || editor.get_text().len() < max_chars.0)
-            {
-                // to have new line on wasm rather than E
-                is_edit = true;
-                editor.action(&mut font_system.0, Action::Insert('\n'));
+/// Keeps track of dead keys and compose sequences between [`KeyboardInput`]s
+#[derive(Default)]
+pub(crate) struct TextInputState {
+    dead_key: Option<char>,
+    composing: bool,
+}
+
+impl TextInputState {
+    /// Works out what text a [`KeyboardInput`] should insert, if any.
+    ///
+    /// Prefers [`KeyboardInput::text`], which already contains the result of
+    /// dead keys and compose sequences on most platforms, and falls back to
+    /// the [`Key::Character`] of the logical key.
+    pub fn text_from(&mut self, input: &KeyboardInput) -> Option<String> {
+        if input.state != ButtonState::Pressed {
+            return None;
+        }
+
+        let text = match (&input.text, &input.logical_key) {
+            (_, Key::Dead(accent)) => {
+                // web never says which accent, but also composes by itself
+                self.dead_key = *accent;
+                return None;
             }
+            (_, Key::Compose) => {
+                self.composing = true;
+                return None;
+            }
+            (Some(text), _) => {
+                self.composing = false;
+                text.to_string()
+            }
+            // keys pressed mid compose sequence have no text until the last one
+            (None, _) if self.composing => return None,
+            (None, Key::Character(c)) => c.to_string(),
+            (None, Key::Space) => " ".to_string(),
+            (None, _) => return None,
+        };
+
+        // e.g. `Backspace` has text "\u{8}"
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        if text.is_empty() {
+            return None;
         }
 
-        if !is_return {
-            for char_ev in char_evr.read() {
-                is_edit = true;
-                if *is_deleting {
-                    editor.action(&mut font_system.0, Action::Backspace);
-                } else if !command
-                    && (max_chars.0 == 0 || editor.get_text().len() < max_chars.0)
-                    && matches!(char_ev.state, bevy::input::ButtonState::Pressed)
-                {
-                    match &char_ev.logical_key {
-                        Key::Character(char) => {
-                            let b = char.as_bytes();
-                            for c in b {
-                                let c: char = (*c).into();
-                                editor.action(&mut font_system.0, Action::Insert(c));
-                            }
-                        }
-                        Key::Space => {
-                            editor.action(&mut font_system.0, Action::Insert(' '));
-                        }
-                        _ => (),
-                    }
-                }
+        Some(match self.dead_key.take() {
+            Some(accent) => compose_dead_key(accent, &text),
+            None => text,
+        })
+    }
+}
+
+/// Applies a dead key to the text typed after it, if the platform hasn't already
+fn compose_dead_key(accent: char, text: &str) -> String {
+    if text == " " {
+        return accent.to_string();
+    }
+
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), combining_mark(accent)) {
+        (Some(base), None, Some(mark)) if base.is_ascii_alphabetic() => {
+            match precomposed(base, mark) {
+                Some(composed) => composed.to_string(),
+                // no single character for it, like NFC leaves it
+                None => [base, mark].into_iter().collect(),
             }
         }
+        // already composed, e.g. "é" or "´x"
+        _ => text.to_string(),
+    }
+}
 
-        if !is_edit {
-            return;
+fn combining_mark(accent: char) -> Option<char> {
+    let mark = match accent {
+        '\u{300}'..='\u{36F}' => accent,
+        '`' => '\u{300}',
+        '´' | '\'' => '\u{301}',
+        '^' | 'ˆ' => '\u{302}',
+        '~' | '˜' => '\u{303}',
+        '¯' => '\u{304}',
+        '˘' => '\u{306}',
+        '˙' => '\u{307}',
+        '¨' | '"' => '\u{308}',
+        '˚' | '°' => '\u{30A}',
+        '˝' => '\u{30B}',
+        'ˇ' => '\u{30C}',
+        '¸' => '\u{327}',
+        '˛' => '\u{328}',
+        _ => return None,
+    };
+    Some(mark)
+}
+
+/// Letters with a precomposed form (NFC) for each combining mark, and those forms
+const PRECOMPOSED: &[(char, &str, &str)] = &[
+    ('\u{300}', "aeinouwyAEINOUWY", "àèìǹòùẁỳÀÈÌǸÒÙẀỲ"),
+    (
+        '\u{301}',
+        "acegiklmnoprsuwyzACEGIKLMNOPRSUWYZ",
+        "áćéǵíḱĺḿńóṕŕśúẃýźÁĆÉǴÍḰĹḾŃÓṔŔŚÚẂÝŹ",
+    ),
+    (
+        '\u{302}',
+        "aceghijosuwyzACEGHIJOSUWYZ",
+        "âĉêĝĥîĵôŝûŵŷẑÂĈÊĜĤÎĴÔŜÛŴŶẐ",
+    ),
+    ('\u{303}', "aeinouvyAEINOUVY", "ãẽĩñõũṽỹÃẼĨÑÕŨṼỸ"),
+    ('\u{304}', "aegiouyAEGIOUY", "āēḡīōūȳĀĒḠĪŌŪȲ"),
+    ('\u{306}', "aegiouAEGIOU", "ăĕğĭŏŭĂĔĞĬŎŬ"),
+    (
+        '\u{307}',
+        "abcdefghmnoprstwxyzABCDEFGHIMNOPRSTWXYZ",
+        "ȧḃċḋėḟġḣṁṅȯṗṙṡṫẇẋẏżȦḂĊḊĖḞĠḢİṀṄȮṖṘṠṪẆẊẎŻ",
+    ),
+    ('\u{308}', "aehiotuwxyAEHIOUWXY", "äëḧïöẗüẅẍÿÄËḦÏÖÜẄẌŸ"),
+    ('\u{30A}', "auwyAU", "åůẘẙÅŮ"),
+    ('\u{30B}', "ouOU", "őűŐŰ"),
+    (
+        '\u{30C}',
+        "acdeghijklnorstuzACDEGHIKLNORSTUZ",
+        "ǎčďěǧȟǐǰǩľňǒřšťǔžǍČĎĚǦȞǏǨĽŇǑŘŠŤǓŽ",
+    ),
+    (
+        '\u{327}',
+        "cdeghklnrstCDEGHKLNRST",
+        "çḑȩģḩķļņŗşţÇḐȨĢḨĶĻŅŖŞŢ",
+    ),
+    ('\u{328}', "aeiouAEIOU", "ąęįǫųĄĘĮǪŲ"),
+];
+
+/// `base` followed by `mark` as a single character, e.g. `é`
+fn precomposed(base: char, mark: char) -> Option<char> {
+    let (_, bases, composed) = PRECOMPOSED.iter().find(|(m, ..)| *m == mark)?;
+    let i = bases.chars().position(|c| c == base)?;
+    composed.chars().nth(i)
+}
+
+/// Components that restrict what can be inserted into a widget
+#[derive(QueryData)]
+pub(crate) struct InsertLimits {
+    max_lines: &'static MaxLines,
+    max_chars: &'static MaxChars,
+    filter: Option<&'static InputFilter>,
+    mask: Option<&'static InputMask>,
+    placeholder: Option<&'static Placeholder>,
+}
+
+/// Inserts `text` at the cursor, replacing the selection, as far as the [`InsertLimits`] allow.
+///
+/// Stops once [`MaxChars`] is reached, and leaves out newlines past [`MaxLines`].
+/// Returns the characters rejected by the [`InputFilter`] or [`InputMask`]
+pub(crate) fn insert_text_limited<'b>(
+    editor: &mut impl Edit<'b>,
+    font_system: &mut FontSystem,
+    text: &str,
+    limits: &InsertLimitsItem,
+) -> String {
+    // placeholder text is still in the buffer until `remove_placeholder_on_input`
+    let placeholder_active = limits.placeholder.is_some_and(Placeholder::is_active);
+
+    if let Some(mask) = limits.mask {
+        return mask::insert(mask, editor, text, placeholder_active, |raw| {
+            limits.filter.is_none_or(|filter| filter.accepts(raw))
+        });
+    }
+
+    editor.delete_selection();
+    let (before, after) = if placeholder_active {
+        Default::default()
+    } else {
+        text_around_cursor(editor)
+    };
+
+    let mut char_count = before.graphemes(true).count() + after.graphemes(true).count();
+    // the text as it would be after inserting the next grapheme at `insert_at`,
+    // updated in place rather than rebuilt for every grapheme of a paste
+    let mut insert_at = before.len();
+    let mut candidate = before + &after;
+    let mut rejected = String::new();
+    for grapheme in text.graphemes(true) {
+        if limits.max_chars.0 != 0 && char_count >= limits.max_chars.0 {
+            break;
+        }
+        let newline = grapheme == "\n" || grapheme == "\r\n";
+        if newline
+            && limits.max_lines.0 != 0
+            && editor.with_buffer(|b| b.lines.len()) >= limits.max_lines.0
+        {
+            continue;
+        }
+        if let Some(filter) = limits.filter {
+            candidate.insert_str(insert_at, grapheme);
+            if !filter.accepts(&candidate) {
+                candidate.replace_range(insert_at..insert_at + grapheme.len(), "");
+                rejected.push_str(grapheme);
+                continue;
+            }
+            insert_at += grapheme.len();
+        }
+
+        if newline {
+            editor.action(font_system, Action::Insert('\n'));
+        } else {
+            for c in grapheme.chars() {
+                editor.action(font_system, Action::Insert(c));
+            }
         }
+        char_count += 1;
+    }
+    rejected
+}
+
+/// Whether [`MaxLines`] leaves room for another line. Masked text is always a single line
+fn newline_fits<'b>(editor: &impl Edit<'b>, limits: &InsertLimitsItem) -> bool {
+    limits.mask.is_none()
+        && (limits.max_lines.0 == 0 || editor.with_buffer(|b| b.lines.len()) < limits.max_lines.0)
+}
+
+/// Backspace (or Delete if `forward`), taking an [`InputMask`] into account
+pub(crate) fn delete_limited<'b>(
+    editor: &mut impl Edit<'b>,
+    font_system: &mut FontSystem,
+    forward: bool,
+    limits: &InsertLimitsItem,
+) {
+    if let Some(mask) = limits.mask {
+        let placeholder_active = limits.placeholder.is_some_and(Placeholder::is_active);
+        mask::delete(mask, editor, forward, placeholder_active);
+    } else if forward {
+        editor.action(font_system, Action::Delete);
+    } else {
+        // fix for issue #8
+        clear_empty_selection(editor);
+        editor.action(font_system, Action::Backspace);
+    }
+}
 
-        evw_changed.write(CosmicTextChanged((
-            entity,
-            editor.with_buffer_mut(|b| b.get_text()),
-        )));
+/// The text of the whole buffer before and after the cursor
+fn text_around_cursor<'b>(editor: &impl Edit<'b>) -> (String, String) {
+    let cursor = editor.cursor();
+    editor.with_buffer(|buffer| {
+        let mut before = String::new();
+        let mut after = String::new();
+        for (i, line) in buffer.lines.iter().enumerate() {
+            let text = line.text();
+            if i < cursor.line {
+                before.push_str(text);
+                before.push('\n');
+            } else if i == cursor.line {
+                let (pre, post) = text.split_at(cursor.index.min(text.len()));
+                before.push_str(pre);
+                after.push_str(post);
+            } else {
+                after.push('\n');
+                after.push_str(text);
+            }
+        }
+        (before, after)
+    })
+}
+
+/// Undo with `Ctrl+Z`, redo with `Ctrl+Shift+Z` or `Ctrl+Y`
+pub(crate) fn kb_undo_redo(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    keymap: Res<CosmicKeymap>,
+    mut cosmic_edit_query: Query<
+        (&mut CosmicEditor, &mut EditHistory, Option<&CosmicKeymap>),
+        Without<ReadOnly>,
+    >,
+    mut edits: TextEditWriter,
+) {
+    let Some(active_editor_entity) = active_editor.0 else {
+        return;
+    };
+    let Ok((mut editor, mut history, keymap_opt)) = cosmic_edit_query.get_mut(active_editor_entity)
+    else {
+        return;
+    };
+
+    let keymap = keymap_opt.unwrap_or(&keymap);
+    let changed = match keymap.just_pressed(&keys) {
+        Some((KeyCommand::Undo, _)) => history.undo(&mut editor.editor),
+        Some((KeyCommand::Redo, _)) => history.redo(&mut editor.editor),
+        _ => false,
+    };
+
+    if changed {
+        edits.write(active_editor_entity, &mut history, || editor.get_text());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::keyboard::NativeKeyCode;
+    use cosmic_text::Metrics;
+
+    use super::*;
+    use crate::change::CosmicTextEdited;
+    #[allow(deprecated)]
+    use crate::input::CosmicTextChanged;
+
+    #[allow(deprecated)]
+    fn test_app(max_chars: usize) -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<FocusedWidget>()
+            .init_resource::<CosmicKeymap>()
+            .add_message::<KeyboardInput>()
+            .add_message::<CosmicTextChanged>()
+            .add_message::<CosmicTextEdited>()
+            .add_message::<SubmitRequest>()
+            .add_systems(Update, kb_input_text);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        let editor = CosmicEditor::clone_from_buffer(&buffer);
+        app.insert_resource(CosmicFontSystem(font_system));
+
+        let entity = app
+            .world_mut()
+            .spawn((buffer, editor, MaxChars(max_chars)))
+            .id();
+        app.insert_resource(FocusedWidget(Some(entity)));
+
+        (app, entity)
+    }
+
+    fn press(logical_key: Key, text: Option<&str>) -> KeyboardInput {
+        KeyboardInput {
+            key_code: KeyCode::Unidentified(NativeKeyCode::Unidentified),
+            logical_key,
+            state: ButtonState::Pressed,
+            text: text.map(Into::into),
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        }
+    }
+
+    fn type_char(text: &str) -> KeyboardInput {
+        press(Key::Character(text.into()), Some(text))
+    }
+
+    fn run(inputs: impl IntoIterator<Item = KeyboardInput>, max_chars: usize) -> String {
+        let (mut app, entity) = test_app(max_chars);
+        for input in inputs {
+            app.world_mut().write_message(input);
+        }
+        app.update();
+        app.world().get::<CosmicEditor>(entity).unwrap().get_text()
+    }
+
+    #[test]
+    fn multi_byte_characters() {
+        let inputs = ["é", "ß", "ж", "😀", "👍🏽"].map(type_char);
+        assert_eq!(run(inputs, 0), "éßж😀👍🏽");
+    }
+
+    #[test]
+    fn falls_back_to_logical_key() {
+        let inputs = [
+            press(Key::Character("ü".into()), None),
+            press(Key::Space, None),
+        ];
+        assert_eq!(run(inputs, 0), "ü ");
+    }
+
+    #[test]
+    fn dead_keys() {
+        // platform already composed the character
+        let composed = [press(Key::Dead(Some('´')), None), type_char("é")];
+        assert_eq!(run(composed, 0), "é");
+
+        // platform left composing up to us
+        let uncomposed = [press(Key::Dead(Some('´')), None), type_char("e")];
+        let text = run(uncomposed, 0);
+        assert_eq!(text, "é");
+        assert_eq!(text.chars().count(), 1);
+
+        // letters without a precomposed form keep the combining mark
+        let combining = [press(Key::Dead(Some('˝')), None), type_char("a")];
+        assert_eq!(run(combining, 0), "a\u{30B}");
+
+        // dead key followed by space types the accent itself
+        let space = [
+            press(Key::Dead(Some('^')), None),
+            press(Key::Space, Some(" ")),
+        ];
+        assert_eq!(run(space, 0), "^");
+    }
+
+    #[test]
+    fn compose_sequence() {
+        let inputs = [
+            press(Key::Compose, None),
+            press(Key::Character("o".into()), None),
+            press(Key::Character("c".into()), None),
+            type_char("©"),
+        ];
+        assert_eq!(run(inputs, 0), "©");
+    }
+
+    #[test]
+    fn max_chars_counts_graphemes() {
+        let inputs = ["é", "e\u{301}", "👍🏽", "x"].map(type_char);
+        assert_eq!(run(inputs, 3), "ée\u{301}👍🏽");
+    }
+
+    #[test]
+    fn filter_checks_the_text_with_each_grapheme() {
+        let (mut app, entity) = test_app(0);
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(InputFilter::Decimal);
+        for input in ["1", ".", "x", "2", ".", "3"].map(type_char) {
+            app.world_mut().write_message(input);
+        }
+        app.update();
+        let text = app.world().get::<CosmicEditor>(entity).unwrap().get_text();
+        assert_eq!(text, "1.23");
     }
 }
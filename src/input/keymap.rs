@@ -0,0 +1,386 @@
+//! Keyboard shortcuts for editing commands.
+//!
+//! A [`CosmicKeymap`] maps [`KeyChord`]s to [`KeyCommand`]s. The resource applies to
+//! every widget, and a [`CosmicKeymap`] component on a widget overrides it for that widget.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::input::keymap::{CosmicKeymap, KeyChord, KeyCommand};
+//!
+//! fn setup(mut keymap: ResMut<CosmicKeymap>) {
+//!     // Ctrl+W is used by the game
+//!     keymap.unbind(KeyChord::new(KeyCode::KeyW).ctrl());
+//!     // no clipboard access at all
+//!     keymap.unbind_command(KeyCommand::Paste);
+//!     // Ctrl+D deletes forward
+//!     keymap.bind(KeyChord::new(KeyCode::KeyD).ctrl(), KeyCommand::DeleteForward);
+//! }
+//! ```
+
+use bevy::platform::collections::HashMap;
+
+use crate::prelude::*;
+
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<CosmicKeymap>()
+        .register_type::<CosmicKeymap>()
+        .register_type::<KeyChord>()
+        .register_type::<KeyCommand>();
+}
+
+/// A named editing command that can be bound to a [`KeyChord`]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCommand {
+    Left,
+    Right,
+    Up,
+    Down,
+    PreviousWord,
+    NextWord,
+    /// Start of the line
+    Home,
+    /// End of the line
+    End,
+    BufferStart,
+    BufferEnd,
+    PageUp,
+    PageDown,
+    SelectAll,
+    /// Clears the selection
+    Escape,
+    /// Backspace
+    DeleteBackward,
+    /// Delete
+    DeleteForward,
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+    /// Focuses the next widget with a [`CosmicTabIndex`](crate::tab_order::CosmicTabIndex)
+    FocusNext,
+    FocusPrevious,
+}
+
+impl KeyCommand {
+    /// Holding Shift while moving the cursor extends the selection
+    pub fn is_motion(self) -> bool {
+        matches!(
+            self,
+            KeyCommand::Left
+                | KeyCommand::Right
+                | KeyCommand::Up
+                | KeyCommand::Down
+                | KeyCommand::PreviousWord
+                | KeyCommand::NextWord
+                | KeyCommand::Home
+                | KeyCommand::End
+                | KeyCommand::BufferStart
+                | KeyCommand::BufferEnd
+                | KeyCommand::PageUp
+                | KeyCommand::PageDown
+        )
+    }
+
+    /// Whether the command still triggers with Shift held,
+    /// if Shift isn't part of the chord
+    fn ignores_shift(self) -> bool {
+        self.is_motion() || matches!(self, KeyCommand::DeleteBackward | KeyCommand::DeleteForward)
+    }
+}
+
+/// A key together with the modifiers that have to be held
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// Cmd on macOS, the Windows key on Windows
+    pub super_key: bool,
+}
+
+impl KeyChord {
+    /// Just `key` without modifiers
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: false,
+            super_key: false,
+        }
+    }
+
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    pub fn super_key(mut self) -> Self {
+        self.super_key = true;
+        self
+    }
+
+    /// `key` with the modifiers currently held down
+    pub fn from_input(key: KeyCode, keys: &ButtonInput<KeyCode>) -> Self {
+        Self {
+            key,
+            ctrl: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+            alt: keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]),
+            shift: keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+            super_key: keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight]),
+        }
+    }
+}
+
+impl From<KeyCode> for KeyChord {
+    fn from(key: KeyCode) -> Self {
+        Self::new(key)
+    }
+}
+
+/// Maps [`KeyChord`]s to [`KeyCommand`]s.
+///
+/// Used as a resource for all widgets, or as a component to override the
+/// resource for a single widget. Defaults to [`CosmicKeymap::macos`] on macOS
+/// and [`CosmicKeymap::windows_linux`] everywhere else.
+#[derive(Resource, Component, Reflect, Debug, Clone, PartialEq)]
+#[reflect(Resource, Component)]
+pub struct CosmicKeymap {
+    bindings: HashMap<KeyChord, KeyCommand>,
+}
+
+impl Default for CosmicKeymap {
+    fn default() -> Self {
+        if platform_is_macos() {
+            Self::macos()
+        } else {
+            Self::windows_linux()
+        }
+    }
+}
+
+fn platform_is_macos() -> bool {
+    #[cfg(target_arch = "wasm32")]
+    return web_sys::window()
+        .unwrap()
+        .navigator()
+        .user_agent()
+        .unwrap_or("NoUA".into())
+        .contains("Macintosh");
+
+    #[cfg(not(target_arch = "wasm32"))]
+    cfg!(target_os = "macos")
+}
+
+impl CosmicKeymap {
+    /// A keymap without any bindings
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::default(),
+        }
+    }
+
+    /// Bindings shared by every preset: arrow keys, Home / End, Page Up / Down,
+    /// Backspace, Delete, Escape and Tab
+    fn common() -> Self {
+        Self::empty()
+            .with(KeyCode::ArrowLeft, KeyCommand::Left)
+            .with(KeyCode::ArrowRight, KeyCommand::Right)
+            .with(KeyCode::ArrowUp, KeyCommand::Up)
+            .with(KeyCode::ArrowDown, KeyCommand::Down)
+            .with(KeyCode::Home, KeyCommand::Home)
+            .with(KeyCode::End, KeyCommand::End)
+            .with(KeyCode::PageUp, KeyCommand::PageUp)
+            .with(KeyCode::PageDown, KeyCommand::PageDown)
+            .with(KeyCode::Backspace, KeyCommand::DeleteBackward)
+            .with(KeyCode::Delete, KeyCommand::DeleteForward)
+            .with(KeyCode::Escape, KeyCommand::Escape)
+            .with(KeyCode::Tab, KeyCommand::FocusNext)
+            .with(
+                KeyChord::new(KeyCode::Tab).shift(),
+                KeyCommand::FocusPrevious,
+            )
+    }
+
+    /// Ctrl based shortcuts, as used on Windows and most Linux desktops
+    pub fn windows_linux() -> Self {
+        use KeyCode::*;
+        Self::common()
+            .with(KeyChord::new(ArrowLeft).ctrl(), KeyCommand::PreviousWord)
+            .with(KeyChord::new(ArrowRight).ctrl(), KeyCommand::NextWord)
+            .with(KeyChord::new(Home).ctrl(), KeyCommand::BufferStart)
+            .with(KeyChord::new(End).ctrl(), KeyCommand::BufferEnd)
+            .with(KeyChord::new(KeyA).ctrl(), KeyCommand::SelectAll)
+            .with(KeyChord::new(KeyC).ctrl(), KeyCommand::Copy)
+            .with(KeyChord::new(Insert).ctrl(), KeyCommand::Copy)
+            .with(KeyChord::new(KeyX).ctrl(), KeyCommand::Cut)
+            .with(KeyChord::new(Delete).shift(), KeyCommand::Cut)
+            .with(KeyChord::new(KeyV).ctrl(), KeyCommand::Paste)
+            .with(KeyChord::new(Insert).shift(), KeyCommand::Paste)
+            .with(KeyChord::new(KeyZ).ctrl(), KeyCommand::Undo)
+            .with(KeyChord::new(KeyZ).ctrl().shift(), KeyCommand::Redo)
+            .with(KeyChord::new(KeyY).ctrl(), KeyCommand::Redo)
+    }
+
+    /// Cmd and Option based shortcuts, as used on macOS
+    pub fn macos() -> Self {
+        use KeyCode::*;
+        Self::common()
+            .with(KeyChord::new(ArrowLeft).alt(), KeyCommand::PreviousWord)
+            .with(KeyChord::new(ArrowRight).alt(), KeyCommand::NextWord)
+            .with(KeyChord::new(ArrowLeft).super_key(), KeyCommand::Home)
+            .with(KeyChord::new(ArrowRight).super_key(), KeyCommand::End)
+            .with(KeyChord::new(ArrowUp).super_key(), KeyCommand::BufferStart)
+            .with(KeyChord::new(ArrowDown).super_key(), KeyCommand::BufferEnd)
+            .with(KeyChord::new(Home).super_key(), KeyCommand::BufferStart)
+            .with(KeyChord::new(End).super_key(), KeyCommand::BufferEnd)
+            .with(KeyChord::new(KeyA).super_key(), KeyCommand::SelectAll)
+            .with(KeyChord::new(KeyC).super_key(), KeyCommand::Copy)
+            .with(KeyChord::new(KeyX).super_key(), KeyCommand::Cut)
+            .with(KeyChord::new(KeyV).super_key(), KeyCommand::Paste)
+            .with(KeyChord::new(KeyZ).super_key(), KeyCommand::Undo)
+            .with(KeyChord::new(KeyZ).super_key().shift(), KeyCommand::Redo)
+    }
+
+    /// Emacs style bindings on top of the usual arrow keys.
+    ///
+    /// Kill and yank map to cut and paste using the system clipboard
+    pub fn emacs() -> Self {
+        use KeyCode::*;
+        Self::common()
+            .with(KeyChord::new(KeyB).ctrl(), KeyCommand::Left)
+            .with(KeyChord::new(KeyF).ctrl(), KeyCommand::Right)
+            .with(KeyChord::new(KeyP).ctrl(), KeyCommand::Up)
+            .with(KeyChord::new(KeyN).ctrl(), KeyCommand::Down)
+            .with(KeyChord::new(KeyB).alt(), KeyCommand::PreviousWord)
+            .with(KeyChord::new(KeyF).alt(), KeyCommand::NextWord)
+            .with(KeyChord::new(KeyA).ctrl(), KeyCommand::Home)
+            .with(KeyChord::new(KeyE).ctrl(), KeyCommand::End)
+            .with(KeyChord::new(Comma).alt().shift(), KeyCommand::BufferStart)
+            .with(KeyChord::new(Period).alt().shift(), KeyCommand::BufferEnd)
+            .with(KeyChord::new(KeyV).alt(), KeyCommand::PageUp)
+            .with(KeyChord::new(KeyV).ctrl(), KeyCommand::PageDown)
+            .with(KeyChord::new(KeyH).ctrl(), KeyCommand::DeleteBackward)
+            .with(KeyChord::new(KeyD).ctrl(), KeyCommand::DeleteForward)
+            .with(KeyChord::new(KeyG).ctrl(), KeyCommand::Escape)
+            .with(KeyChord::new(KeyH).alt(), KeyCommand::SelectAll)
+            .with(KeyChord::new(KeyW).alt(), KeyCommand::Copy)
+            .with(KeyChord::new(KeyW).ctrl(), KeyCommand::Cut)
+            .with(KeyChord::new(KeyY).ctrl(), KeyCommand::Paste)
+            .with(KeyChord::new(Slash).ctrl(), KeyCommand::Undo)
+            .with(KeyChord::new(Slash).ctrl().shift(), KeyCommand::Redo)
+    }
+
+    /// Builder version of [`CosmicKeymap::bind`]
+    pub fn with(mut self, chord: impl Into<KeyChord>, command: KeyCommand) -> Self {
+        self.bind(chord, command);
+        self
+    }
+
+    /// Binds `chord` to `command`, replacing whatever `chord` was bound to
+    pub fn bind(&mut self, chord: impl Into<KeyChord>, command: KeyCommand) {
+        self.bindings.insert(chord.into(), command);
+    }
+
+    /// Removes the binding for `chord`, returning the command it was bound to
+    pub fn unbind(&mut self, chord: impl Into<KeyChord>) -> Option<KeyCommand> {
+        self.bindings.remove(&chord.into())
+    }
+
+    /// Removes every binding for `command`, disabling it
+    pub fn unbind_command(&mut self, command: KeyCommand) {
+        self.bindings.retain(|_, bound| *bound != command);
+    }
+
+    /// The command bound to exactly this chord
+    pub fn get(&self, chord: impl Into<KeyChord>) -> Option<KeyCommand> {
+        self.bindings.get(&chord.into()).copied()
+    }
+
+    /// All chords bound to `command`
+    pub fn chords_for(&self, command: KeyCommand) -> impl Iterator<Item = KeyChord> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, bound)| **bound == command)
+            .map(|(chord, _)| *chord)
+    }
+
+    /// Resolves a chord to a command.
+    ///
+    /// If Shift isn't part of any matching binding, it is ignored for motions and deletions.
+    /// The returned `bool` is `true` when a motion should extend the selection.
+    pub fn resolve(&self, chord: KeyChord) -> Option<(KeyCommand, bool)> {
+        if let Some(command) = self.get(chord) {
+            return Some((command, false));
+        }
+        if !chord.shift {
+            return None;
+        }
+        let command = self.get(KeyChord {
+            shift: false,
+            ..chord
+        })?;
+        command
+            .ignores_shift()
+            .then_some((command, command.is_motion()))
+    }
+
+    /// The first command triggered by a key pressed this frame, see [`CosmicKeymap::resolve`]
+    pub fn just_pressed(&self, keys: &ButtonInput<KeyCode>) -> Option<(KeyCommand, bool)> {
+        keys.get_just_pressed()
+            .find_map(|key| self.resolve(KeyChord::from_input(*key, keys)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_extends_motions_only() {
+        let keymap = CosmicKeymap::windows_linux();
+
+        assert_eq!(
+            keymap.resolve(KeyChord::new(KeyCode::ArrowLeft).ctrl().shift()),
+            Some((KeyCommand::PreviousWord, true))
+        );
+        assert_eq!(
+            keymap.resolve(KeyChord::new(KeyCode::KeyZ).ctrl().shift()),
+            Some((KeyCommand::Redo, false))
+        );
+        assert_eq!(
+            keymap.resolve(KeyChord::new(KeyCode::Backspace).shift()),
+            Some((KeyCommand::DeleteBackward, false))
+        );
+        assert_eq!(
+            keymap.resolve(KeyChord::new(KeyCode::KeyC).ctrl().shift()),
+            None
+        );
+    }
+
+    #[test]
+    fn rebinding() {
+        let mut keymap = CosmicKeymap::emacs();
+        keymap.unbind_command(KeyCommand::Paste);
+        assert_eq!(keymap.chords_for(KeyCommand::Paste).count(), 0);
+
+        keymap.bind(KeyChord::new(KeyCode::KeyY).ctrl(), KeyCommand::Redo);
+        assert_eq!(
+            keymap.resolve(KeyChord::new(KeyCode::KeyY).ctrl()),
+            Some((KeyCommand::Redo, false))
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Submitting a widget with Enter, see [`EnterBehavior`] and [`CosmicTextSubmitted`]
+
+use cosmic_text::{Action, Cursor, Edit, Motion, Selection};
+
+use crate::{
+    change::TextEditWriter,
+    history::{EditHistory, EditOrigin},
+    password::PasswordSet,
+    placeholder::Placeholder,
+    prelude::*,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    // the real text of a `Password` is only back in the buffer after `InputSet`
+    app.add_systems(PostUpdate, submit.before(PasswordSet))
+        .add_message::<SubmitRequest>()
+        .add_message::<CosmicTextSubmitted>()
+        .register_type::<EnterBehavior>();
+}
+
+/// What pressing Enter does in a widget.
+///
+/// Enter always submits once [`MaxLines`](crate::MaxLines) (or an
+/// [`InputMask`](crate::mask::InputMask)) leaves no room for another line,
+/// so single-line fields submit even with [`EnterBehavior::Newline`]
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum EnterBehavior {
+    /// Inserts a newline
+    #[default]
+    Newline,
+    /// Sends [`CosmicTextSubmitted`]
+    Submit,
+    /// Sends [`CosmicTextSubmitted`], or inserts a newline while Shift is held
+    SubmitUnlessShift,
+    /// Sends [`CosmicTextSubmitted`] and then clears the text, e.g. for chat boxes
+    SubmitAndClear,
+    /// Sends [`CosmicTextSubmitted`] and then unfocuses the widget
+    SubmitAndUnfocus,
+}
+
+impl EnterBehavior {
+    /// Whether Enter submits rather than inserting a newline
+    pub fn submits(&self, shift: bool) -> bool {
+        match self {
+            EnterBehavior::Newline => false,
+            EnterBehavior::SubmitUnlessShift => !shift,
+            EnterBehavior::Submit
+            | EnterBehavior::SubmitAndClear
+            | EnterBehavior::SubmitAndUnfocus => true,
+        }
+    }
+}
+
+/// Sent when a widget is submitted with Enter, see [`EnterBehavior`].
+///
+/// Both triggered on the widget, for observers, and written as a message
+#[derive(Message, EntityEvent, Reflect, Debug, Clone)]
+pub struct CosmicTextSubmitted {
+    pub entity: Entity,
+    /// The text at the time it was submitted, empty while a
+    /// [`Placeholder`] is shown
+    pub text: String,
+}
+
+/// Enter was pressed in a widget that submits
+#[derive(Message, Debug)]
+pub(crate) struct SubmitRequest(pub Entity);
+
+fn submit(
+    mut requests: MessageReader<SubmitRequest>,
+    mut widgets: Query<(
+        EditorBuffer,
+        &mut EditHistory,
+        Option<&EnterBehavior>,
+        Option<&Placeholder>,
+    )>,
+    mut focused_widget: ResMut<FocusedWidget>,
+    mut commands: Commands,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut evw_submitted: MessageWriter<CosmicTextSubmitted>,
+    mut edits: TextEditWriter,
+) {
+    for SubmitRequest(entity) in requests.read() {
+        let entity = *entity;
+        let Ok((mut buffer, mut history, behavior_opt, placeholder_opt)) = widgets.get_mut(entity)
+        else {
+            continue;
+        };
+
+        let text = if placeholder_opt.is_some_and(Placeholder::is_active) {
+            String::new()
+        } else {
+            buffer.get_text()
+        };
+        let submitted = CosmicTextSubmitted { entity, text };
+        evw_submitted.write(submitted.clone());
+        commands.trigger(submitted);
+
+        match behavior_opt.copied().unwrap_or_default() {
+            EnterBehavior::SubmitAndClear => {
+                let cleared = buffer.with_editor_mut(|editor| {
+                    history.begin(editor);
+                    editor.set_selection(Selection::Normal(Cursor::new(0, 0)));
+                    editor.action(&mut font_system.0, Action::Motion(Motion::BufferEnd));
+                    editor.delete_selection();
+                    editor.set_redraw(true);
+                    history.commit(editor, EditOrigin::Programmatic)
+                });
+                if cleared {
+                    edits.write(entity, &mut history, String::new);
+                }
+            }
+            // goes through `FocusChangeRequest`, so observers can still veto it
+            EnterBehavior::SubmitAndUnfocus if focused_widget.is_focused(entity) => {
+                focused_widget.unfocus();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    };
+    use cosmic_text::{Attrs, Metrics};
+
+    use super::*;
+    #[allow(deprecated)]
+    use crate::input::CosmicTextChanged;
+    use crate::{
+        change::CosmicTextEdited, input::keyboard::kb_input_text, input::keymap::CosmicKeymap,
+        MaxChars, MaxLines,
+    };
+
+    #[allow(deprecated)]
+    fn test_app(behavior: EnterBehavior, max_lines: usize) -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<CosmicKeymap>()
+            .add_message::<KeyboardInput>()
+            .add_message::<CosmicTextChanged>()
+            .add_message::<CosmicTextEdited>()
+            .add_message::<SubmitRequest>()
+            .add_message::<CosmicTextSubmitted>()
+            .add_systems(Update, kb_input_text)
+            .add_systems(PostUpdate, submit);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.)).with_text(
+            &mut font_system,
+            "hi",
+            Attrs::new(),
+        );
+        let mut editor = CosmicEditor::clone_from_buffer(&buffer);
+        editor.set_cursor(Cursor::new(0, 2));
+        app.insert_resource(CosmicFontSystem(font_system));
+
+        let entity = app
+            .world_mut()
+            .spawn((buffer, editor, behavior, MaxLines(max_lines), MaxChars(0)))
+            .id();
+        app.insert_resource(FocusedWidget(Some(entity)));
+
+        (app, entity)
+    }
+
+    fn press_enter(app: &mut App) {
+        app.world_mut().write_message(KeyboardInput {
+            key_code: KeyCode::Enter,
+            logical_key: Key::Enter,
+            state: ButtonState::Pressed,
+            text: Some("\r".into()),
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+    }
+
+    fn submitted(app: &App) -> Vec<String> {
+        app.world()
+            .resource::<Messages<CosmicTextSubmitted>>()
+            .iter_current_update_messages()
+            .map(|submitted| submitted.text.clone())
+            .collect()
+    }
+
+    #[test]
+    fn max_lines_submits_single_line_fields() {
+        let (mut app, entity) = test_app(EnterBehavior::Newline, 0);
+        press_enter(&mut app);
+        assert!(submitted(&app).is_empty());
+        assert_eq!(
+            app.world().get::<CosmicEditor>(entity).unwrap().get_text(),
+            "hi\n"
+        );
+
+        let (mut app, _) = test_app(EnterBehavior::Newline, 1);
+        press_enter(&mut app);
+        assert_eq!(submitted(&app), ["hi"]);
+    }
+
+    #[test]
+    fn clears_or_unfocuses_after_submit() {
+        let (mut app, entity) = test_app(EnterBehavior::SubmitAndClear, 0);
+        press_enter(&mut app);
+        assert_eq!(submitted(&app), ["hi"]);
+        assert_eq!(
+            app.world().get::<CosmicEditor>(entity).unwrap().get_text(),
+            ""
+        );
+
+        let (mut app, _) = test_app(EnterBehavior::SubmitAndUnfocus, 0);
+        press_enter(&mut app);
+        assert_eq!(app.world().resource::<FocusedWidget>().0, None);
+    }
+
+    #[test]
+    fn unfocus_after_submit_can_be_vetoed() {
+        use crate::focus::{FocusChangeRequest, FocusLost};
+
+        let (mut app, entity) = test_app(EnterBehavior::SubmitAndUnfocus, 0);
+        app.add_plugins(crate::focus::plugin);
+        app.update();
+        app.add_observer(|mut request: On<FocusChangeRequest>| {
+            request.event_mut().veto();
+        });
+        app.world_mut()
+            .entity_mut(entity)
+            .observe(|_: On<FocusLost>| panic!("focus was vetoed"));
+
+        press_enter(&mut app);
+        assert_eq!(submitted(&app), ["hi"]);
+        // in case the focus systems ran before `submit`
+        app.update();
+        assert_eq!(app.world().resource::<FocusedWidget>().0, Some(entity));
+    }
+}
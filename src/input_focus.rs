@@ -0,0 +1,155 @@
+//! Sharing focus with [`bevy::input_focus`], enabled by the `input_focus` feature.
+//!
+//! [`FocusedWidget`] and bevy's [`InputFocus`] are kept in sync, so buttons, sliders
+//! and text widgets never look focused at the same time:
+//!
+//! - Focusing a widget, e.g. by clicking it or with [`CosmicTabIndex`], sets [`InputFocus`]
+//! - Setting [`InputFocus`] to a widget focuses it, so
+//!   [directional navigation](bevy::input_focus::directional_navigation) and bevy's
+//!   [`TabIndex`](bevy::input_focus::tab_navigation::TabIndex) can land on text widgets
+//! - Setting [`InputFocus`] to anything else unfocuses the widget
+//!
+//! Keyboard input dispatched to a focused widget as [`FocusedInput`] doesn't bubble up to
+//! its parents or the window, as the widget has used it. The exception is Tab and
+//! Shift+Tab in widgets without a [`CosmicTabIndex`], which are left to bevy's tab
+//! navigation. Use [`no_editor_focused`] to keep your own navigation from reacting to
+//! keys typed into a widget.
+//!
+//! [`FocusedInput`] is only sent with bevy's [`InputDispatchPlugin`], which
+//! [`CosmicEditPlugin`](crate::CosmicEditPlugin) adds unless the app already has it.
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input_focus::{FocusedInput, InputDispatchPlugin, InputFocus};
+
+use crate::{
+    focus::FocusSet,
+    input::keymap::{CosmicKeymap, KeyChord, KeyCommand},
+    prelude::*,
+    tab_order::CosmicTabIndex,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    // once for changes made since the last frame, and again to undo vetoed changes
+    app.add_systems(
+        PostUpdate,
+        (
+            sync_input_focus.before(FocusSet),
+            sync_input_focus.after(FocusSet),
+        ),
+    )
+    .add_observer(consume_keys)
+    .init_resource::<InputFocus>()
+    .init_resource::<SyncedFocus>();
+}
+
+/// Called from [`Plugin::finish`], so apps can add [`InputDispatchPlugin`] before or
+/// after [`CosmicEditPlugin`](crate::CosmicEditPlugin)
+pub(crate) fn add_input_dispatch(app: &mut App) {
+    if !app.is_plugin_added::<InputDispatchPlugin>() {
+        app.add_plugins(InputDispatchPlugin);
+    }
+}
+
+/// Run condition that is `true` unless a text widget is focused
+pub fn no_editor_focused(focused_widget: Res<FocusedWidget>) -> bool {
+    focused_widget.0.is_none()
+}
+
+/// The focus that [`FocusedWidget`] and [`InputFocus`] last agreed on
+#[derive(Resource, Default)]
+struct SyncedFocus(Option<Entity>);
+
+fn sync_input_focus(
+    mut synced: ResMut<SyncedFocus>,
+    mut focused_widget: ResMut<FocusedWidget>,
+    mut input_focus: ResMut<InputFocus>,
+    widgets: Query<(), With<CosmicEditBuffer>>,
+) {
+    if focused_widget.0 != synced.0 {
+        // focus moved between widgets or was dropped by this crate
+        let focus = match focused_widget.0 {
+            Some(widget) => Some(widget),
+            // something else may have been focused since
+            None if input_focus.0 == synced.0 => None,
+            None => input_focus.0,
+        };
+        if input_focus.0 != focus {
+            input_focus.0 = focus;
+        }
+    } else if input_focus.0 != synced.0 {
+        let widget = input_focus.0.filter(|entity| widgets.contains(*entity));
+        if focused_widget.0 != widget {
+            focused_widget.0 = widget;
+        }
+    }
+    synced.0 = focused_widget.0;
+}
+
+fn consume_keys(
+    mut input: On<FocusedInput<KeyboardInput>>,
+    editors: Query<(Option<&CosmicKeymap>, Has<CosmicTabIndex>), With<CosmicEditor>>,
+    keymap: Res<CosmicKeymap>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    let Ok((keymap_opt, tab_index)) = editors.get(input.event_target()) else {
+        return;
+    };
+    let chord = KeyChord::from_input(input.input.key_code, &keys);
+    let tabbing = matches!(
+        keymap_opt.unwrap_or(&keymap).resolve(chord),
+        Some((KeyCommand::FocusNext | KeyCommand::FocusPrevious, _))
+    );
+    if tab_index || !tabbing {
+        input.propagate(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::Metrics;
+
+    use super::*;
+
+    #[test]
+    fn follows_input_focus() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_plugins((crate::focus::plugin, plugin));
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let widget = app.world_mut().spawn(buffer).id();
+        let button = app.world_mut().spawn_empty().id();
+
+        app.world_mut().resource_mut::<InputFocus>().set(widget);
+        app.update();
+        assert_eq!(app.world().resource::<FocusedWidget>().0, Some(widget));
+        assert!(app.world().get::<CosmicEditor>(widget).is_some());
+
+        app.world_mut().resource_mut::<InputFocus>().set(button);
+        app.update();
+        assert_eq!(app.world().resource::<FocusedWidget>().0, None);
+        assert!(app.world().get::<CosmicEditor>(widget).is_none());
+
+        app.world_mut()
+            .resource_mut::<FocusedWidget>()
+            .focus(widget);
+        app.update();
+        assert_eq!(app.world().resource::<InputFocus>().0, Some(widget));
+
+        app.world_mut().resource_mut::<FocusedWidget>().unfocus();
+        app.update();
+        assert_eq!(app.world().resource::<InputFocus>().0, None);
+    }
+
+    #[test]
+    fn adds_input_dispatch_only_if_missing() {
+        let mut app = App::new();
+        add_input_dispatch(&mut app);
+        assert!(app.is_plugin_added::<InputDispatchPlugin>());
+
+        // would panic if added twice
+        add_input_dispatch(&mut app);
+    }
+}
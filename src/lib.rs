@@ -62,7 +62,6 @@ pub mod prelude {
     pub(crate) use bevy::prelude::*;
     // Explicitly import logging macros to avoid shadowing by #[warn] lint attribute
     pub(crate) use bevy::log::{debug, trace, warn, warn_once};
-    pub(crate) use bevy::text::SwashCache;
     pub(crate) use cosmic_text::Buffer;
     pub(crate) use cosmic_text::Edit as _;
     #[allow(unused_imports)]
@@ -77,16 +76,21 @@ pub mod prelude {
 
     // public internal re-exports
     pub use crate::buffer::CosmicEditBuffer; // todo: migrate to builtin bevy CosmicBuffer
+    pub use crate::command::{CosmicEditCommand, EditCommand};
     pub use crate::cosmic_edit::CosmicFontSystem; // todo: migrate to using builtin bevy cosmic font system
-    pub use crate::cosmic_edit::{CosmicWrap, DefaultAttrs, ReadOnly};
+    pub use crate::cosmic_edit::{CosmicWrap, DefaultAttrs, Disabled, ReadOnly};
     pub use crate::cosmic_text::{Color as CosmicColor, Style as FontStyle, Weight as FontWeight};
     pub use crate::editor::CosmicEditor;
     pub use crate::editor_buffer::EditorBuffer;
-    pub use crate::focus::FocusedWidget;
+    pub use crate::focus::{FocusPolicy, FocusedWidget};
+    pub use crate::history::EditHistory;
     pub use crate::input::click::focus_on_click;
+    pub use crate::input::submit::{CosmicTextSubmitted, EnterBehavior};
     pub use crate::primary::{CosmicEditPlugin, CosmicFontConfig};
     pub use crate::render_implementations::{TextEdit, TextEdit2d};
-    pub use crate::utils::{deselect_editor_on_esc, print_editor_text, ColorExtras as _};
+    #[allow(deprecated)]
+    pub use crate::utils::deselect_editor_on_esc;
+    pub use crate::utils::{print_editor_text, ColorExtras as _};
 }
 
 // required modules
@@ -94,8 +98,10 @@ pub mod prelude {
 pub use buffer::*;
 pub use cosmic_edit::*;
 pub use editor_buffer::*;
-pub use editor_buffer::{buffer, editor};
+pub use editor_buffer::{buffer, editor, history};
 pub use focus::*;
+pub mod change;
+pub mod command;
 mod cosmic_edit;
 mod double_click;
 mod editor_buffer;
@@ -109,8 +115,17 @@ pub mod render_implementations;
 pub mod utils;
 
 // extra modules
+pub mod auto_size;
+pub mod filter;
+#[cfg(feature = "input_focus")]
+pub mod input_focus;
+pub mod mask;
 pub mod password;
 pub mod placeholder;
+pub mod selection;
+pub mod style;
+pub mod tab_order;
+pub mod theme;
 pub mod user_select;
 
 #[cfg(feature = "internal-debugging")]
@@ -0,0 +1,461 @@
+//! Formatted input like phone numbers, dates or serial keys.
+//!
+//! An [`InputMask`] describes which characters go where. Separators are inserted
+//! automatically, the cursor skips over them and Backspace / Delete only remove
+//! characters the user typed. The buffer holds the formatted text, the
+//! [`MaskedValue`] component has both the raw and the formatted value.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::mask::{InputMask, MaskedValue};
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn((
+//!         TextEdit,
+//!         CosmicEditBuffer::default(),
+//!         InputMask::new("(###) ###-####"),
+//!     ));
+//! }
+//!
+//! fn read_phone_number(q: Query<&MaskedValue, Changed<MaskedValue>>) {
+//!     for value in q.iter() {
+//!         // "5551234567" and "(555) 123-4567"
+//!         info!(raw = value.raw, formatted = value.formatted, complete = value.complete);
+//!     }
+//! }
+//! ```
+
+use cosmic_text::{Cursor, Edit, Selection};
+
+use crate::{filter::InputValidity, password::PasswordSet, placeholder::Placeholder, prelude::*};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(PostUpdate, update_masked_value.before(PasswordSet))
+        .register_type::<MaskedValue>();
+}
+
+/// One position of an [`InputMask`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MaskSlot {
+    /// `#`
+    Digit,
+    /// `A`
+    Letter,
+    /// `X`
+    Alphanumeric,
+    /// Anything else, inserted automatically
+    Literal(char),
+}
+
+impl MaskSlot {
+    fn accepts(self, c: char) -> bool {
+        match self {
+            MaskSlot::Digit => c.is_ascii_digit(),
+            MaskSlot::Letter => c.is_alphabetic(),
+            MaskSlot::Alphanumeric => c.is_alphanumeric(),
+            MaskSlot::Literal(_) => false,
+        }
+    }
+}
+
+/// Formats a widget's text according to a pattern:
+///
+/// - `#` is a digit
+/// - `A` is a letter
+/// - `X` is a letter or digit
+/// - `\` makes the next character a literal, e.g. `\#`
+/// - everything else is a literal separator
+///
+/// Masked widgets are single line, and can be combined with an
+/// [`InputFilter`](crate::filter::InputFilter) that is checked against the raw value.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+#[require(InputValidity, MaskedValue)]
+pub struct InputMask {
+    slots: Vec<MaskSlot>,
+}
+
+impl InputMask {
+    pub fn new(pattern: &str) -> Self {
+        let mut slots = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            slots.push(match c {
+                '#' => MaskSlot::Digit,
+                'A' => MaskSlot::Letter,
+                'X' => MaskSlot::Alphanumeric,
+                '\\' => MaskSlot::Literal(chars.next().unwrap_or('\\')),
+                c => MaskSlot::Literal(c),
+            });
+        }
+        Self { slots }
+    }
+
+    fn inputs(&self) -> impl Iterator<Item = MaskSlot> + '_ {
+        self.slots
+            .iter()
+            .copied()
+            .filter(|slot| !matches!(slot, MaskSlot::Literal(_)))
+    }
+
+    /// How many characters the user can type
+    pub fn capacity(&self) -> usize {
+        self.inputs().count()
+    }
+
+    fn is_literal(&self, c: char) -> bool {
+        self.slots.contains(&MaskSlot::Literal(c))
+    }
+
+    /// Whether every character of `raw` fits the slot it would end up in
+    fn fits(&self, raw: &[char]) -> bool {
+        raw.len() <= self.capacity()
+            && raw
+                .iter()
+                .zip(self.inputs())
+                .all(|(c, slot)| slot.accepts(*c))
+    }
+
+    /// The characters typed by the user, leaving out separators.
+    ///
+    /// Works on formatted as well as unformatted text
+    pub fn raw(&self, text: &str) -> String {
+        let mut raw = String::new();
+        let mut slots = self.slots.iter().peekable();
+        'chars: for c in text.chars() {
+            while let Some(MaskSlot::Literal(literal)) = slots.peek() {
+                slots.next();
+                if *literal == c {
+                    continue 'chars;
+                }
+            }
+            match slots.peek() {
+                Some(slot) if slot.accepts(c) => {
+                    raw.push(c);
+                    slots.next();
+                }
+                _ => {}
+            }
+        }
+        raw
+    }
+
+    /// Adds separators to raw user input. Separators following the last
+    /// character are included, so the cursor ends up where typing continues.
+    pub fn format(&self, raw: &str) -> String {
+        let mut formatted = String::new();
+        let mut raw = raw.chars().peekable();
+        if raw.peek().is_none() {
+            return formatted;
+        }
+        for slot in &self.slots {
+            match slot {
+                MaskSlot::Literal(literal) => formatted.push(*literal),
+                _ => match raw.next() {
+                    Some(c) => formatted.push(c),
+                    None => break,
+                },
+            }
+        }
+        formatted
+    }
+
+    /// Whether every slot is filled
+    pub fn is_complete(&self, raw: &str) -> bool {
+        raw.chars().count() == self.capacity()
+    }
+
+    /// Byte index in the formatted text after `count` raw characters
+    fn position(&self, raw: &[char], count: usize) -> usize {
+        if count == 0 {
+            return 0;
+        }
+        let prefix: String = raw[..count.min(raw.len())].iter().collect();
+        self.format(&prefix).len()
+    }
+
+    /// Number of raw characters before byte index `index` of `formatted`
+    fn raw_count(&self, formatted: &str, index: usize) -> usize {
+        let index = index.min(formatted.len());
+        formatted
+            .get(..index)
+            .map_or(0, |prefix| self.raw(prefix).chars().count())
+    }
+
+    /// The range of raw characters covered by the cursor and selection
+    fn raw_range(&self, formatted: &str, cursor: usize, anchor: Option<usize>) -> (usize, usize) {
+        let cursor = self.raw_count(formatted, cursor);
+        let anchor = anchor.map_or(cursor, |anchor| self.raw_count(formatted, anchor));
+        (cursor.min(anchor), cursor.max(anchor))
+    }
+
+    /// Inserts `text` at the cursor.
+    ///
+    /// `accepts` is checked for every candidate raw value.
+    /// Returns the new formatted text, its cursor and the rejected characters.
+    fn insert(
+        &self,
+        formatted: &str,
+        cursor: usize,
+        anchor: Option<usize>,
+        text: &str,
+        accepts: impl Fn(&str) -> bool,
+    ) -> (String, usize, String) {
+        let mut raw: Vec<char> = self.raw(formatted).chars().collect();
+        let (start, end) = self.raw_range(formatted, cursor, anchor);
+        raw.drain(start..end);
+
+        let mut index = start;
+        let mut rejected = String::new();
+        for c in text.chars() {
+            if raw.len() >= self.capacity() {
+                break;
+            }
+            let mut candidate = raw.clone();
+            candidate.insert(index, c);
+            if self.fits(&candidate) && accepts(&candidate.iter().collect::<String>()) {
+                raw = candidate;
+                index += 1;
+            } else if !self.is_literal(c) {
+                // typing the separators yourself is fine
+                rejected.push(c);
+            }
+        }
+
+        let raw_text: String = raw.iter().collect();
+        (self.format(&raw_text), self.position(&raw, index), rejected)
+    }
+
+    /// Deletes the selection, or one user character before or after the cursor
+    fn delete(
+        &self,
+        formatted: &str,
+        cursor: usize,
+        anchor: Option<usize>,
+        forward: bool,
+    ) -> (String, usize) {
+        let mut raw: Vec<char> = self.raw(formatted).chars().collect();
+        let (mut start, mut end) = self.raw_range(formatted, cursor, anchor);
+        if start == end {
+            if forward {
+                end = (end + 1).min(raw.len());
+            } else {
+                start = start.saturating_sub(1);
+            }
+        }
+        raw.drain(start..end);
+
+        let raw_text: String = raw.iter().collect();
+        (self.format(&raw_text), self.position(&raw, start))
+    }
+
+    /// Moves the cursor by one user character, skipping separators
+    fn step(&self, formatted: &str, cursor: usize, forward: bool) -> usize {
+        let raw: Vec<char> = self.raw(formatted).chars().collect();
+        let count = self.raw_count(formatted, cursor);
+        let count = if forward {
+            (count + 1).min(raw.len())
+        } else {
+            count.saturating_sub(1)
+        };
+        self.position(&raw, count)
+    }
+
+    /// Moves a cursor that ended up between separators to where typing would continue
+    fn snap(&self, formatted: &str, cursor: usize) -> usize {
+        let raw: Vec<char> = self.raw(formatted).chars().collect();
+        self.position(&raw, self.raw_count(formatted, cursor))
+    }
+}
+
+/// The value of a widget with an [`InputMask`], updated whenever it changes
+#[derive(Component, Reflect, Debug, Default, Clone, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct MaskedValue {
+    /// Only the characters typed by the user
+    pub raw: String,
+    /// With separators, as displayed
+    pub formatted: String,
+    /// Whether every slot of the mask is filled
+    pub complete: bool,
+}
+
+fn update_masked_value(
+    mut q: Query<(
+        EditorBuffer,
+        &InputMask,
+        &mut MaskedValue,
+        Option<&Placeholder>,
+    )>,
+) {
+    for (buffer, mask, mut value, placeholder_opt) in q.iter_mut() {
+        let text = if placeholder_opt.is_some_and(Placeholder::is_active) {
+            String::new()
+        } else {
+            buffer.get_text()
+        };
+        let raw = mask.raw(&text);
+        let new_value = MaskedValue {
+            complete: mask.is_complete(&raw),
+            formatted: mask.format(&raw),
+            raw,
+        };
+        value.set_if_neq(new_value);
+    }
+}
+
+/// Text, cursor and selection anchor of a masked widget as byte indices.
+///
+/// `placeholder_active` makes the widget count as empty.
+fn masked_state<'b>(
+    editor: &impl Edit<'b>,
+    placeholder_active: bool,
+) -> (String, usize, Option<usize>) {
+    if placeholder_active {
+        return (String::new(), 0, None);
+    }
+    let text = editor.with_buffer(|buffer| buffer.get_text());
+    let offset = |cursor: Cursor| {
+        editor.with_buffer(|buffer| {
+            buffer.lines[..cursor.line.min(buffer.lines.len())]
+                .iter()
+                .map(|line| line.text().len() + 1)
+                .sum::<usize>()
+                + cursor.index
+        })
+    };
+    let anchor = match editor.selection() {
+        Selection::None => None,
+        Selection::Normal(anchor) | Selection::Line(anchor) | Selection::Word(anchor) => {
+            Some(offset(anchor))
+        }
+    };
+    (text, offset(editor.cursor()), anchor)
+}
+
+/// Replaces the whole text of `editor`, only touching the part that changed
+fn set_masked_text<'b>(editor: &mut impl Edit<'b>, new_text: &str, cursor: usize) {
+    let (lines, old_text) =
+        editor.with_buffer(|buffer| (buffer.lines.len(), buffer.lines[0].text().to_owned()));
+    editor.set_selection(Selection::None);
+
+    if lines > 1 {
+        let end = editor.with_buffer(|buffer| {
+            let last = buffer.lines.len() - 1;
+            Cursor::new(last, buffer.lines[last].text().len())
+        });
+        editor.delete_range(Cursor::new(0, 0), end);
+        editor.insert_at(Cursor::new(0, 0), new_text, None);
+    } else if old_text != new_text {
+        let prefix = old_text
+            .char_indices()
+            .zip(new_text.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8());
+        let suffix = old_text[prefix..]
+            .chars()
+            .rev()
+            .zip(new_text[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum::<usize>();
+        editor.delete_range(
+            Cursor::new(0, prefix),
+            Cursor::new(0, old_text.len() - suffix),
+        );
+        editor.insert_at(
+            Cursor::new(0, prefix),
+            &new_text[prefix..new_text.len() - suffix],
+            None,
+        );
+    }
+    editor.set_cursor(Cursor::new(0, cursor.min(new_text.len())));
+    editor.set_redraw(true);
+}
+
+/// Mask aware version of inserting text at the cursor, see
+/// [`insert_text_limited`](crate::input::keyboard::insert_text_limited)
+pub(crate) fn insert<'b>(
+    mask: &InputMask,
+    editor: &mut impl Edit<'b>,
+    text: &str,
+    placeholder_active: bool,
+    accepts: impl Fn(&str) -> bool,
+) -> String {
+    let (formatted, cursor, anchor) = masked_state(editor, placeholder_active);
+    let (new_text, cursor, rejected) = mask.insert(&formatted, cursor, anchor, text, accepts);
+    set_masked_text(editor, &new_text, cursor);
+    rejected
+}
+
+/// Mask aware version of Backspace and Delete
+pub(crate) fn delete<'b>(
+    mask: &InputMask,
+    editor: &mut impl Edit<'b>,
+    forward: bool,
+    placeholder_active: bool,
+) {
+    if placeholder_active {
+        return;
+    }
+    let (formatted, cursor, anchor) = masked_state(editor, false);
+    let (new_text, cursor) = mask.delete(&formatted, cursor, anchor, forward);
+    set_masked_text(editor, &new_text, cursor);
+}
+
+/// Moves the cursor by one user character, skipping separators
+pub(crate) fn step_cursor<'b>(mask: &InputMask, editor: &mut impl Edit<'b>, forward: bool) {
+    let cursor = editor.cursor();
+    if cursor.line != 0 {
+        return;
+    }
+    let text = editor.with_buffer(|buffer| buffer.lines[0].text().to_owned());
+    editor.set_cursor(Cursor::new(0, mask.step(&text, cursor.index, forward)));
+}
+
+/// Moves a cursor that ended up between separators to where typing would continue
+pub(crate) fn snap_cursor<'b>(mask: &InputMask, editor: &mut impl Edit<'b>) {
+    let cursor = editor.cursor();
+    if cursor.line != 0 {
+        return;
+    }
+    let text = editor.with_buffer(|buffer| buffer.lines[0].text().to_owned());
+    let index = mask.snap(&text, cursor.index);
+    if index != cursor.index {
+        editor.set_cursor(Cursor::new(0, index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_as_you_type() {
+        let mask = InputMask::new("(###) ###-####");
+        assert_eq!(mask.capacity(), 10);
+        assert_eq!(mask.format(""), "");
+        assert_eq!(mask.format("555"), "(555) ");
+        assert_eq!(mask.format("5551234567"), "(555) 123-4567");
+        assert_eq!(mask.raw("(555) 123-4567"), "5551234567");
+        assert_eq!(mask.raw("5551234567"), "5551234567");
+
+        let (text, cursor, rejected) = mask.insert("(555) 1", 7, None, "2a3-4", |_| true);
+        assert_eq!(text, "(555) 123-4");
+        assert_eq!(cursor, text.len());
+        assert_eq!(rejected, "a");
+    }
+
+    #[test]
+    fn backspace_skips_separators() {
+        let mask = InputMask::new("XXXX-XXXX");
+        let (text, cursor) = mask.delete("AB12-C", 6, None, false);
+        assert_eq!((text.as_str(), cursor), ("AB12-", 5));
+        let (text, cursor) = mask.delete(&text, cursor, None, false);
+        assert_eq!((text.as_str(), cursor), ("AB1", 3));
+
+        assert_eq!(mask.step("AB12-CD", 5, false), 3);
+        assert_eq!(mask.step("AB12-CD", 3, true), 5);
+    }
+}
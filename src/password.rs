@@ -95,6 +95,8 @@ fn hide_password_text(
                 continue;
             }
         }
+        // swapping in the blockers doesn't change what the widget looks like
+        let redraw = editor.redraw();
         match editor.editor() {
             Some(editor) => {
                 let mut cursor = editor.cursor();
@@ -156,6 +158,7 @@ fn hide_password_text(
                 password.real_text = text;
             }
         }
+        editor.set_redraw(redraw);
     }
 }
 
@@ -177,6 +180,7 @@ fn restore_password_text(
             }
         }
         if let Some(mut editor) = editor_opt {
+            let redraw = editor.redraw();
             let mut cursor = editor.cursor();
             let mut selection = editor.selection();
 
@@ -223,14 +227,17 @@ fn restore_password_text(
 
             editor.set_cursor(cursor);
             editor.set_selection(selection);
+            editor.set_redraw(redraw);
 
             continue;
         }
 
+        let redraw = buffer.redraw();
         buffer.set_text(
             &mut font_system,
             password.real_text.as_str(),
             attrs.as_attrs(),
         );
+        buffer.set_redraw(redraw);
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    cosmic_edit::DefaultAttrs, input::CosmicTextChanged, input::InputSet, prelude::*,
+    change::CosmicTextEdited, cosmic_edit::DefaultAttrs, input::InputSet, prelude::*,
     render::RenderSet,
 };
 use cosmic_text::{Attrs, Edit};
@@ -124,7 +124,7 @@ fn move_cursor_to_start_of_placeholder(mut q: Query<(&mut CosmicEditor, &mut Pla
 
 fn remove_placeholder_on_input(
     mut q: Query<(&mut CosmicEditor, &mut Placeholder, &DefaultAttrs)>,
-    evr: MessageReader<CosmicTextChanged>,
+    evr: MessageReader<CosmicTextEdited>,
     mut font_system: ResMut<CosmicFontSystem>,
 ) {
     for (mut editor, mut placeholder, attrs) in q.iter_mut() {
@@ -221,8 +221,8 @@ fn remove_placeholder_on_input(
         };
 
         editor.set_cursor(cosmic_text::Cursor::new(
-            (lines - 1).max(0),
-            last_line.bytes().len(),
+            lines.saturating_sub(1),
+            last_line.len(),
         ));
 
         placeholder.active = false;
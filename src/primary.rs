@@ -20,10 +20,21 @@ impl Plugin for CosmicEditPlugin {
             crate::editor_buffer::plugin,
             crate::render::plugin,
             crate::input::plugin,
+            crate::change::plugin,
+            crate::command::plugin,
             crate::focus::plugin,
-            crate::placeholder::plugin,
-            crate::password::plugin,
-            crate::user_select::plugin,
+            (
+                crate::auto_size::plugin,
+                crate::placeholder::plugin,
+                crate::filter::plugin,
+                crate::mask::plugin,
+                crate::password::plugin,
+                crate::selection::plugin,
+                crate::style::plugin,
+                crate::tab_order::plugin,
+                crate::theme::plugin,
+                crate::user_select::plugin,
+            ),
             crate::double_click::plugin,
         ))
         // TODO: Use the builtin bevy CosmicFontSystem
@@ -31,9 +42,17 @@ impl Plugin for CosmicEditPlugin {
 
         app.register_type::<CosmicRenderOutput>();
 
+        #[cfg(feature = "input_focus")]
+        app.add_plugins(crate::input_focus::plugin);
+
         #[cfg(feature = "internal-debugging")]
         app.add_plugins(crate::debug::plugin);
     }
+
+    #[cfg(feature = "input_focus")]
+    fn finish(&self, app: &mut App) {
+        crate::input_focus::add_input_dispatch(app);
+    }
 }
 
 /// Resource struct that holds configuration options for cosmic fonts.
@@ -95,7 +114,8 @@ fn create_cosmic_font_system(cosmic_font_config: CosmicFontConfig) -> cosmic_tex
 
 #[cfg(test)]
 mod tests {
- 
+    use bevy::input::keyboard::KeyboardInput;
+
     use super::*;
 
     fn test_spawn_cosmic_edit_system(
@@ -109,4 +129,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_spawn_cosmic_edit() {
+        let mut app = App::new();
+        app.add_plugins(TaskPoolPlugin::default());
+        app.add_plugins(AssetPlugin::default());
+        app.init_asset::<Image>();
+        app.insert_resource(CosmicFontSystem(create_cosmic_font_system(
+            CosmicFontConfig::default(),
+        )));
+        app.add_systems(Update, test_spawn_cosmic_edit_system);
+
+        let input = ButtonInput::<KeyCode>::default();
+        app.insert_resource(input);
+        let mouse_input: ButtonInput<MouseButton> = ButtonInput::<MouseButton>::default();
+        app.insert_resource(mouse_input);
+
+        app.add_message::<KeyboardInput>();
+
+        app.update();
+
+        let mut text_nodes_query = app.world_mut().query::<&CosmicEditBuffer>();
+        for cosmic_editor in text_nodes_query.iter(app.world()) {
+            insta::assert_debug_snapshot!(cosmic_editor
+                .inner()
+                .lines
+                .iter()
+                .map(|line| line.text())
+                .collect::<Vec<_>>());
+        }
+    }
 }
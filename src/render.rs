@@ -1,8 +1,20 @@
-use crate::{cosmic_edit::ReadOnly, prelude::*};
-use crate::{cosmic_edit::*, BufferMutExtras};
+use crate::input::ime::{ImePreedit, PreeditOverlay};
+use crate::{cosmic_edit::ReadOnly, prelude::*, style::StyledTextColor};
+use crate::{cosmic_edit::*, BufferMutExtras, CosmicPadding, EditorBufferItem};
+use atlas::{render_glyph_quads, GlyphAtlas};
+use bevy::ecs::{entity::EntityHashSet, system::SystemParam};
+use bevy::platform::collections::HashSet;
 use bevy::render::render_resource::Extent3d;
+use bevy::sprite::Anchor;
+use bevy::utils::Parallel;
+use cosmic_text::{Affinity, LayoutRun, Selection};
 use image::{imageops::FilterType, GenericImageView};
-use render_implementations::CosmicWidgetSize;
+use rasterize::{GlyphFonts, GlyphRasterizer};
+use render_implementations::{CosmicWidgetSize, CosmicWidgetSizeItem, WidgetScaleFactor};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+mod atlas;
+mod rasterize;
 
 /// System set for cosmic text rendering systems. Runs in [`PostUpdate`]
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
@@ -13,7 +25,193 @@ pub(crate) fn plugin(app: &mut App) {
         First,
         update_internal_target_handles.pipe(render_implementations::debug_error),
     )
-    .add_systems(PostUpdate, (render_texture,).in_set(RenderSet));
+    .add_systems(
+        PostUpdate,
+        (redraw_changed_widgets, render_texture, render_glyph_quads)
+            .chain()
+            .in_set(RenderSet),
+    )
+    .init_resource::<GlyphAtlas>();
+}
+
+/// Everything [`render_texture`] reads, besides the text itself.
+///
+/// Text, cursor and selection changes set [`Buffer::redraw`] instead
+type ChangedRenderInputs = Or<(
+    Changed<CosmicRenderOutput>,
+    Changed<DefaultAttrs>,
+    Changed<CosmicBackgroundImage>,
+    Changed<CosmicBackgroundColor>,
+    Changed<CursorColor>,
+    Changed<SelectionColor>,
+    Changed<SelectedTextColor>,
+    Changed<InactiveSelectionColor>,
+    Changed<StyledTextColor>,
+    Changed<ReadOnly>,
+    Changed<Disabled>,
+    Changed<CosmicTextAlign>,
+    Changed<CosmicWrap>,
+    Changed<CosmicRenderMode>,
+    Or<(
+        Changed<CosmicPadding>,
+        Changed<Sprite>,
+        Changed<Anchor>,
+        Changed<ComputedNode>,
+        Changed<WidgetScaleFactor>,
+    )>,
+)>;
+
+/// The optional parts of [`ChangedRenderInputs`] that were removed
+#[derive(SystemParam)]
+struct RemovedRenderInputs<'w, 's> {
+    selected_text_color: RemovedComponents<'w, 's, SelectedTextColor>,
+    inactive_selection_color: RemovedComponents<'w, 's, InactiveSelectionColor>,
+    styled_text_color: RemovedComponents<'w, 's, StyledTextColor>,
+    readonly: RemovedComponents<'w, 's, ReadOnly>,
+    disabled: RemovedComponents<'w, 's, Disabled>,
+    padding: RemovedComponents<'w, 's, CosmicPadding>,
+    render_mode: RemovedComponents<'w, 's, CosmicRenderMode>,
+}
+
+impl RemovedRenderInputs<'_, '_> {
+    fn read(&mut self) -> EntityHashSet {
+        let mut removed = EntityHashSet::default();
+        removed.extend(self.selected_text_color.read());
+        removed.extend(self.inactive_selection_color.read());
+        removed.extend(self.styled_text_color.read());
+        removed.extend(self.readonly.read());
+        removed.extend(self.disabled.read());
+        removed.extend(self.padding.read());
+        removed.extend(self.render_mode.read());
+        removed
+    }
+}
+
+/// What [`render_texture`] drew last time, so text changes only redraw the lines that
+/// changed and cursor blinks only redraw the line with the cursor.
+///
+/// Automatically added to every [`CosmicEditBuffer`]
+#[derive(Component, Default)]
+pub(crate) struct RenderCache {
+    /// Set when anything besides the text changed, see [`redraw_changed_widgets`]
+    full_redraw: bool,
+    size: UVec2,
+    offset: Vec2,
+    runs: Vec<DrawnRun>,
+    /// Set while the widget is being rasterized
+    job: Option<RenderJob>,
+}
+
+impl RenderCache {
+    /// Which rows of the widget show runs that were added, removed or changed
+    fn changed_rows(&self, runs: &[DrawnRun], offset_y: f32, height: usize) -> Vec<bool> {
+        let old: HashSet<u64> = self.runs.iter().map(|run| run.signature).collect();
+        let new: HashSet<u64> = runs.iter().map(|run| run.signature).collect();
+        let mut rows = vec![false; height];
+        let changed = self
+            .runs
+            .iter()
+            .filter(|run| !new.contains(&run.signature))
+            .chain(runs.iter().filter(|run| !old.contains(&run.signature)));
+        for run in changed {
+            let start = ((run.top + offset_y).floor().max(0.) as usize).min(height);
+            let end = ((run.bottom + offset_y).ceil().max(0.) as usize).min(height);
+            rows[start..end.max(start)].fill(true);
+        }
+        rows
+    }
+}
+
+/// A drawn [`LayoutRun`], in buffer coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DrawnRun {
+    /// Covers glyphs that reach outside the line height
+    top: f32,
+    bottom: f32,
+    /// Hash of everything that goes into drawing the run
+    signature: u64,
+}
+
+impl DrawnRun {
+    fn new(
+        run: &LayoutRun,
+        selection: Option<(cosmic_text::Cursor, cosmic_text::Cursor)>,
+        cursor: Option<cosmic_text::Cursor>,
+        preedit: Option<&PreeditOverlay>,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        run.line_i.hash(&mut hasher);
+        for value in [run.line_y, run.line_top, run.line_height, run.line_w] {
+            value.to_bits().hash(&mut hasher);
+        }
+        let mut font_size = 0f32;
+        for glyph in run.glyphs {
+            font_size = font_size.max(glyph.font_size);
+            glyph.font_id.hash(&mut hasher);
+            glyph.glyph_id.hash(&mut hasher);
+            for value in [glyph.x, glyph.y, glyph.w, glyph.font_size] {
+                value.to_bits().hash(&mut hasher);
+            }
+            glyph.color_opt.hash(&mut hasher);
+        }
+        selection.is_some().hash(&mut hasher);
+        if let Some((start, end)) = selection {
+            (start.line.cmp(&run.line_i), end.line.cmp(&run.line_i)).hash(&mut hasher);
+            run.highlight(start, end)
+                .map(|(x, w)| (x.to_bits(), w.to_bits()))
+                .hash(&mut hasher);
+        }
+        if let Some(cursor) = cursor.filter(|cursor| cursor.line == run.line_i) {
+            cursor.index.hash(&mut hasher);
+            (cursor.affinity == Affinity::After).hash(&mut hasher);
+        }
+        if let Some(preedit) = preedit.filter(|preedit| preedit.line() == run.line_i) {
+            preedit.signature().hash(&mut hasher);
+        }
+
+        Self {
+            top: run.line_top.min(run.line_y - font_size),
+            bottom: (run.line_top + run.line_height).max(run.line_y + font_size / 2.),
+            signature: hasher.finish(),
+        }
+    }
+}
+
+/// Sets [`Buffer::redraw`] on widgets whose [`ChangedRenderInputs`] or
+/// background image changed, so [`render_texture`] only draws those.
+/// These changes redraw the whole widget, see [`RenderCache`]
+fn redraw_changed_widgets(
+    mut widgets: Query<(
+        Entity,
+        EditorBuffer,
+        &CosmicBackgroundImage,
+        &mut RenderCache,
+    )>,
+    changed: Query<(), ChangedRenderInputs>,
+    mut removed: RemovedRenderInputs,
+    mut image_events: MessageReader<AssetEvent<Image>>,
+) {
+    let images: HashSet<AssetId<Image>> = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let removed = removed.read();
+
+    for (entity, mut editor, background_image, mut cache) in widgets.iter_mut() {
+        let background_changed = background_image
+            .0
+            .as_ref()
+            .is_some_and(|image| images.contains(&image.id()));
+        if changed.contains(entity) || removed.contains(&entity) || background_changed {
+            editor.set_redraw(true);
+            cache.full_redraw = true;
+        }
+    }
 }
 
 /// Every frame updates the output (in [`CosmicRenderOutput`]) to its receiver
@@ -31,6 +229,13 @@ fn update_internal_target_handles(
     Ok(())
 }
 
+/// Fills RGBA `pixels` with `color`
+fn fill_pixels(pixels: &mut [u8], color: cosmic_text::Color) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&color.as_rgba());
+    }
+}
+
 fn draw_pixel(
     buffer: &mut [u8],
     width: i32,
@@ -57,6 +262,22 @@ fn draw_pixel(
 
     let offset = (y as usize * width as usize + x as usize) * 4;
 
+    if a_a == 255 {
+        // Opaque pixels replace the background
+        buffer[offset..offset + 4].copy_from_slice(&color.as_rgba());
+        return;
+    }
+
+    if buffer[offset + 3] == 255 {
+        // Blend onto an opaque background in integers
+        let blend =
+            |fg: u8, bg: u8| ((fg as u32 * a_a + bg as u32 * (255 - a_a) + 127) / 255) as u8;
+        buffer[offset] = blend(color.r(), buffer[offset]);
+        buffer[offset + 1] = blend(color.g(), buffer[offset + 1]);
+        buffer[offset + 2] = blend(color.b(), buffer[offset + 2]);
+        return;
+    }
+
     let bg = bevy::prelude::Color::srgba_u8(
         buffer[offset],
         buffer[offset + 1],
@@ -64,8 +285,6 @@ fn draw_pixel(
         buffer[offset + 3],
     );
 
-    // TODO: if alpha is 100% or bg is empty skip blending
-
     let fg = Srgba::rgba_u8(color.r(), color.g(), color.b(), color.a());
 
     let premul = (fg * fg.alpha).with_alpha(color.a() as f32 / 255.0);
@@ -79,23 +298,31 @@ fn draw_pixel(
 }
 
 pub(crate) struct WidgetBufferCoordTransformation {
-    /// Padding between the top of the render target and the
-    /// top of the buffer
-    top_padding: f32,
+    /// Offset from the top left of the render target to the
+    /// top left of the buffer, from padding and vertical alignment
+    offset: Vec2,
 
     render_target_size: Vec2,
 }
 
 impl WidgetBufferCoordTransformation {
-    pub fn new(vertical_align: VerticalAlign, render_target_size: Vec2, buffer_size: Vec2) -> Self {
+    /// `content` is the area within the render target the buffer is placed in,
+    /// see [`CosmicWidgetSizeItem::physical_content_rect`](render_implementations::CosmicWidgetSizeItem::physical_content_rect).
+    /// All sizes are in physical pixels, like the buffer
+    pub fn new(
+        vertical_align: VerticalAlign,
+        render_target_size: Vec2,
+        content: Rect,
+        buffer_size: Vec2,
+    ) -> Self {
         let top_padding = match vertical_align {
             VerticalAlign::Top => 0.0,
-            VerticalAlign::Bottom => (render_target_size.y - buffer_size.y).max(0.0),
-            VerticalAlign::Center => ((render_target_size.y - buffer_size.y) / 2.0).max(0.0),
+            VerticalAlign::Bottom => (content.height() - buffer_size.y).max(0.0),
+            VerticalAlign::Center => ((content.height() - buffer_size.y) / 2.0).max(0.0),
         };
         // debug!(?top_padding, ?render_target_height, ?buffer_height);
         Self {
-            top_padding,
+            offset: content.min + Vec2::new(0.0, top_padding),
             render_target_size,
         }
     }
@@ -103,7 +330,7 @@ impl WidgetBufferCoordTransformation {
     /// If you have the buffer coord, used for rendering
     // Confusing ngl, but it works
     pub fn buffer_to_widget(&self, buffer: Vec2) -> Vec2 {
-        Vec2::new(buffer.x, buffer.y + self.top_padding)
+        buffer + self.offset
     }
 
     /// If you have the relative widget coord centered (0, 0) in the middle of the widget,
@@ -111,258 +338,945 @@ impl WidgetBufferCoordTransformation {
     pub fn widget_origined_to_buffer_topleft(&self, widget: Vec2) -> Vec2 {
         Vec2::new(
             widget.x + self.render_target_size.x / 2.,
-            -widget.y + self.render_target_size.y / 2. - self.top_padding,
-        )
+            -widget.y + self.render_target_size.y / 2.,
+        ) - self.offset
     }
 
     pub fn widget_topleft_to_buffer_topleft(&self, widget: Vec2) -> Vec2 {
-        Vec2::new(widget.x, widget.y - self.top_padding)
+        widget - self.offset
     }
 
     #[allow(dead_code)]
-    pub(crate) fn debug_top_padding(&self) {
-        debug!(?self.top_padding);
+    pub(crate) fn debug_offset(&self) {
+        debug!(?self.offset);
     }
 }
 
-/// Renders to the [CosmicRenderOutput]
+/// Draws a line under the text between `start` and `end`, used for IME preedit text
+pub(crate) fn draw_underline(
+    buffer: &Buffer,
+    start: cosmic_text::Cursor,
+    end: cosmic_text::Cursor,
+    color: cosmic_text::Color,
+    mut f: impl FnMut(i32, i32, u32, u32, cosmic_text::Color),
+) {
+    let thickness = (buffer.metrics().font_size / 14.).ceil().max(1.);
+    for run in buffer.layout_runs() {
+        let Some((x, w)) = run.highlight(start, end) else {
+            continue;
+        };
+        let y = (run.line_y + thickness).min(run.line_top + run.line_height - thickness);
+        f(
+            x as i32,
+            y as i32,
+            w.max(1.) as u32,
+            thickness as u32,
+            color,
+        );
+    }
+}
+
+/// Draws glyphs like [`cosmic_text::LegacyRenderer`], optionally in a single color
+struct WidgetRenderer<'a, F> {
+    fonts: &'a GlyphFonts,
+    rasterizer: &'a mut GlyphRasterizer,
+    /// Replaces glyph colors, except for text drawn in `selected_text_color`
+    text_color: Option<cosmic_text::Color>,
+    selected_text_color: Option<cosmic_text::Color>,
+    /// Area that is drawn to, in buffer coordinates. Anything outside is skipped
+    visible: IRect,
+    callback: F,
+}
+
+impl<F: FnMut(i32, i32, u32, u32, cosmic_text::Color)> cosmic_text::Renderer
+    for WidgetRenderer<'_, F>
+{
+    fn rectangle(&mut self, x: i32, y: i32, w: u32, h: u32, color: cosmic_text::Color) {
+        let rect = IRect::new(x, y, x + w as i32, y + h as i32);
+        if rect.intersect(self.visible).is_empty() {
+            return;
+        }
+        (self.callback)(x, y, w, h, color);
+    }
+
+    fn glyph(&mut self, physical_glyph: cosmic_text::PhysicalGlyph, color: cosmic_text::Color) {
+        if let Some(image) = self
+            .rasterizer
+            .get_image(self.fonts, physical_glyph.cache_key)
+        {
+            let min = IVec2::new(
+                physical_glyph.x + image.placement.left,
+                physical_glyph.y - image.placement.top,
+            );
+            let size = IVec2::new(image.placement.width as i32, image.placement.height as i32);
+            if IRect::from_corners(min, min + size)
+                .intersect(self.visible)
+                .is_empty()
+            {
+                return;
+            }
+        }
+        let color = glyph_color(color, self.text_color, self.selected_text_color);
+        self.rasterizer.with_pixels(
+            self.fonts,
+            physical_glyph.cache_key,
+            color,
+            |x, y, pixel_color| {
+                (self.callback)(
+                    physical_glyph.x + x,
+                    physical_glyph.y + y,
+                    1,
+                    1,
+                    pixel_color,
+                );
+            },
+        );
+    }
+}
+
+/// `text_color` replaces the color of glyphs, except for text drawn in `selected_text_color`
+fn glyph_color(
+    color: cosmic_text::Color,
+    text_color: Option<cosmic_text::Color>,
+    selected_text_color: Option<cosmic_text::Color>,
+) -> cosmic_text::Color {
+    match text_color {
+        Some(text_color) if Some(color) != selected_text_color => text_color,
+        _ => color,
+    }
+}
+
+/// Colors a widget is drawn with, resolved from its components
+#[derive(Clone, Copy)]
+struct RenderColors {
+    font: cosmic_text::Color,
+    /// From [`StyledTextColor`], replaces colors from the text's attributes
+    text_override: Option<cosmic_text::Color>,
+    /// Transparent while the cursor is hidden
+    cursor: cosmic_text::Color,
+    selection: cosmic_text::Color,
+    selected_text: Option<cosmic_text::Color>,
+    inactive_selection: Option<cosmic_text::Color>,
+}
+
+impl RenderColors {
+    fn new(
+        attrs: &DefaultAttrs,
+        cursor_color: &CursorColor,
+        selection_color: &SelectionColor,
+        selected_text_color: Option<&SelectedTextColor>,
+        inactive_selection_color: Option<&InactiveSelectionColor>,
+        styled_text_color: Option<&StyledTextColor>,
+        cursor_visible: bool,
+    ) -> Self {
+        // a styled text color also replaces colors from the text's attributes
+        let text_override = styled_text_color.map(|color| color.0.to_cosmic());
+        let font = text_override.unwrap_or(
+            attrs
+                .0
+                .color_opt
+                .unwrap_or(cosmic_text::Color::rgb(0, 0, 0)),
+        );
+        let cursor_opacity = if cursor_visible {
+            cursor_color.0.alpha()
+        } else {
+            0.
+        };
+        Self {
+            font,
+            text_override,
+            cursor: cursor_color.0.with_alpha(cursor_opacity).to_cosmic(),
+            selection: selection_color.0.to_cosmic(),
+            selected_text: selected_text_color.map(|color| color.0.to_cosmic()),
+            inactive_selection: inactive_selection_color.map(|color| color.0.to_cosmic()),
+        }
+    }
+
+    /// Glyphs in this color keep it instead of `text_override`
+    fn selected_text_override(&self, focused: bool) -> Option<cosmic_text::Color> {
+        self.selected_text.filter(|_| focused)
+    }
+}
+
+/// Disabled widgets fade out everything drawn over the background
+fn disabled_color(color: cosmic_text::Color) -> cosmic_text::Color {
+    let [r, g, b, a] = color.as_rgba();
+    cosmic_text::Color::rgba(r, g, b, a / 2)
+}
+
+/// Draws the text, cursor and selection of a widget with `renderer`,
+/// the same way for every [`CosmicRenderMode`]
+fn draw_text(
+    editor: &mut EditorBufferItem,
+    renderer: &mut impl cosmic_text::Renderer,
+    colors: &RenderColors,
+    preedit: Option<&PreeditOverlay>,
+) {
+    let Some(preedit) = preedit else {
+        draw_editor(editor, renderer, colors);
+        return;
+    };
+    // the preedit takes the place of the cursor and selection while it is composed
+    let hidden = cosmic_text::Color::rgba(0, 0, 0, 0);
+    let composing = RenderColors {
+        cursor: hidden,
+        selection: hidden,
+        selected_text: None,
+        inactive_selection: None,
+        ..*colors
+    };
+    draw_editor(editor, &mut preedit.make_room(renderer), &composing);
+    preedit.draw(renderer, colors.font, colors.cursor);
+}
+
+fn draw_editor(
+    editor: &mut EditorBufferItem,
+    renderer: &mut impl cosmic_text::Renderer,
+    colors: &RenderColors,
+) {
+    if let Some(editor) = editor.editor() {
+        // try to fix annoying scroll behaviour
+        // by only allowing vertical scrolling if the buffer is actually larger than the canvas
+        // let mut scroll = editor.with_buffer(|b| b.scroll());
+        // if buffer_size.y + 10.0 < render_target_size.y {
+        //     trace!(
+        //         message = "Ignoring vertical scroll as buffer is smaller than canvas",
+        //         ?buffer_size.y,
+        //         ?render_target_size.y
+        //     );
+        //     scroll.vertical = 0.0;
+        // }
+        // editor.with_buffer_mut(|b| b.set_scroll(scroll));
+
+        // let new_buffer_size = editor.expected_size();
+
+        editor.render(
+            renderer,
+            colors.font,
+            colors.cursor,
+            colors.selection,
+            colors.selected_text.unwrap_or(colors.font),
+        );
+
+        // if coord calculations seem to be buggy, this code may help you to debug
+        // let actually_rendered_buffer_size = actually_rendered_max - actually_rendered_min;
+        // trace!(
+        //     ?buffer_size,
+        //     ?new_buffer_size,
+        //     ?actually_rendered_buffer_size
+        // );
+        // transformation.debug_offset();
+        // debug check only
+        // if (new_buffer_size.as_ivec2() - actually_rendered_buffer_size)
+        //     .as_vec2()
+        //     .length()
+        //     > 5.0
+        // {
+        //     warn_once!(
+        //         message = "Calculations of buffer sizes are off by a significant amount",
+        //         note = "This is likely an internal bug with bevy_cosmic_edit"
+        //     );
+        // }
+    } else {
+        // editor.borrow_with(font_system).compute_everything();
+        match colors.inactive_selection {
+            Some(selection_color) if editor.selection() != Selection::None => {
+                let no_cursor = cosmic_text::Color::rgba(0, 0, 0, 0);
+                editor.with_editor_mut(|editor| {
+                    editor.render(
+                        renderer,
+                        colors.font,
+                        no_cursor,
+                        selection_color,
+                        colors.font,
+                    )
+                });
+            }
+            _ => editor.render(renderer, colors.font),
+        }
+    }
+}
+
+/// Fits the buffer of a widget to its content area.
+///
+/// Returns the physical size of the widget and of its content area, unless it has no size
+fn fit_buffer(
+    editor: &mut EditorBufferItem,
+    font_system: &mut cosmic_text::FontSystem,
+    size: &CosmicWidgetSizeItem,
+    text_align: &CosmicTextAlign,
+    wrap: &CosmicWrap,
+) -> Option<(Vec2, Rect)> {
+    let render_target_size = size.physical_size().ok()?;
+
+    // avoids a panic
+    if render_target_size.x == 0. || render_target_size.y == 0. {
+        debug!(
+            message = "Size of buffer is zero, skipping",
+            // once = "This log only appears once"
+        );
+        return None;
+    }
+
+    let content = size.physical_content_rect().ok()?;
+    // BUG: overflow when using center/right/end aligned infinite wrap
+    let buffer_size = (
+        Some(match wrap {
+            CosmicWrap::Wrap => content.width(),
+            // probably high enough
+            CosmicWrap::InfiniteLine => f32::MAX / 10f32.powi(3),
+        }),
+        Some(content.height()),
+    );
+    // only through `DerefMut` if something differs, which marks the widget as changed
+    if editor.size() != buffer_size {
+        editor.set_size(font_system, buffer_size.0, buffer_size.1);
+    }
+    if let Some(alignment) = text_align.horizontal {
+        let align = Some(alignment.into());
+        if editor.lines.iter().any(|line| line.align() != align) {
+            for line in &mut editor.lines {
+                line.set_align(align);
+            }
+        }
+    }
+    Some((render_target_size, content))
+}
+
+/// Shapes what is about to be drawn, which can still scroll the buffer.
+///
+/// Returns where the buffer is placed in the widget, and the area it is clipped to
+fn shape_for_drawing(
+    editor: &mut EditorBufferItem,
+    font_system: &mut cosmic_text::FontSystem,
+    text_align: &CosmicTextAlign,
+    render_target_size: Vec2,
+    content: Rect,
+) -> (WidgetBufferCoordTransformation, IRect) {
+    match editor.editor() {
+        Some(editor) => editor.borrow_with(font_system).shape_as_needed(false),
+        None => editor.shape_until_scroll(font_system, false),
+    }
+
+    // compute offset from padding and alignment
+    let buffer_size = editor.borrow_with(font_system).expected_size();
+    let transformation = WidgetBufferCoordTransformation::new(
+        text_align.vertical,
+        render_target_size,
+        content,
+        buffer_size,
+    );
+    let clip = IRect::from_corners(
+        content.min.floor().as_ivec2(),
+        content.max.ceil().as_ivec2(),
+    );
+    (transformation, clip)
+}
+
+/// The background of a widget, `size` is in physical pixels
+fn draw_background(
+    images: &Assets<Image>,
+    background_image: &CosmicBackgroundImage,
+    fill_color: &CosmicBackgroundColor,
+    size: UVec2,
+) -> Vec<u8> {
+    let mut pixels = vec![0; size.x as usize * size.y as usize * 4];
+    if let Some(bg_image) = background_image.0.clone() {
+        if let Some(image) = images.get(&bg_image) {
+            let mut dynamic_image = image.clone().try_into_dynamic().unwrap();
+            if image.size() != size {
+                dynamic_image = dynamic_image.resize_to_fill(size.x, size.y, FilterType::Triangle);
+            }
+            for (i, (_, _, rgba)) in dynamic_image.pixels().enumerate() {
+                if let Some(p) = pixels.get_mut(i * 4..(i + 1) * 4) {
+                    p[0] = rgba[0];
+                    p[1] = rgba[1];
+                    p[2] = rgba[2];
+                    p[3] = rgba[3];
+                }
+            }
+        }
+    } else {
+        fill_pixels(&mut pixels, fill_color.0.to_cosmic());
+    }
+    pixels
+}
+
+/// Updates the stored asset image with the computed pixels
+fn upload_pixels(
+    images: &mut Assets<Image>,
+    canvas: &CosmicRenderOutput,
+    pixels: Vec<u8>,
+    size: UVec2,
+) {
+    if let Some(prev_image) = images.get_mut(&canvas.0) {
+        prev_image.data = Some(pixels);
+        prev_image.resize(Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        });
+    }
+}
+
+/// A widget that [`render_texture`] prepared on the main thread, waiting to be rasterized.
+///
+/// Rasterizing only needs the widget itself, so this can happen on any thread
+struct RenderJob {
+    pixels: Vec<u8>,
+    size: UVec2,
+    /// Only these rows of `pixels` are drawn, the others are up to date
+    redraw_rows: Vec<bool>,
+    transformation: WidgetBufferCoordTransformation,
+    /// The content area of the widget, in widget coordinates
+    clip: IRect,
+    /// The redrawn part of `clip`, in buffer coordinates
+    visible: IRect,
+    colors: RenderColors,
+    disabled: bool,
+    preedit: Option<PreeditOverlay>,
+    runs: Vec<DrawnRun>,
+}
+
+impl RenderJob {
+    /// Draws the text, cursor and selection of `editor` into [`Self::pixels`]
+    fn rasterize(
+        &mut self,
+        editor: &mut EditorBufferItem,
+        fonts: &GlyphFonts,
+        rasterizer: &mut GlyphRasterizer,
+    ) {
+        let Self {
+            pixels,
+            size,
+            redraw_rows,
+            transformation,
+            clip,
+            visible,
+            colors,
+            disabled,
+            preedit,
+            ..
+        } = self;
+
+        // let mut actually_rendered_max = IVec2::ZERO;
+        // let mut actually_rendered_min = IVec2::new(i32::MAX, i32::MAX);
+        let mut draw_closure = |x, y, w, h, color: cosmic_text::Color| {
+            let color = if *disabled {
+                disabled_color(color)
+            } else {
+                color
+            };
+            for row in 0..h as i32 {
+                for col in 0..w as i32 {
+                    let buffer_coord = IVec2::new(x + col, y + row);
+                    // actually_rendered_max = actually_rendered_max.max(buffer_coord);
+                    // actually_rendered_min = actually_rendered_min.min(buffer_coord);
+
+                    // compute padding_top
+                    let widget_coord = transformation
+                        .buffer_to_widget(buffer_coord.as_vec2())
+                        .as_ivec2();
+                    // scrolled text doesn't spill into the padding
+                    if widget_coord.cmplt(clip.min).any() || widget_coord.cmpge(clip.max).any() {
+                        continue;
+                    }
+                    // the other rows are already up to date
+                    if !redraw_rows[widget_coord.y as usize] {
+                        continue;
+                    }
+
+                    // actually draw pixel
+                    draw_pixel(
+                        pixels,
+                        size.x as i32,
+                        size.y as i32,
+                        widget_coord.x,
+                        widget_coord.y,
+                        color,
+                    );
+                }
+            }
+        };
+
+        // Draw glyphs
+        let focused = editor.editor().is_some();
+        draw_text(
+            editor,
+            &mut WidgetRenderer {
+                fonts,
+                rasterizer,
+                text_color: colors.text_override,
+                selected_text_color: colors.selected_text_override(focused),
+                visible: *visible,
+                callback: &mut draw_closure,
+            },
+            colors,
+            preedit.as_ref(),
+        );
+    }
+}
+
+/// Renders to the [CosmicRenderOutput], at the physical size of the widget.
+///
+/// Only widgets with [`Buffer::redraw`] set are drawn, see [`redraw_changed_widgets`].
+/// Text is shaped on the main thread, then the widgets are rasterized as configured
+/// by [`RenderThreads`] and uploaded to their images
 fn render_texture(
     mut query: Query<(
         EditorBuffer,
         &DefaultAttrs,
         &CosmicBackgroundImage,
-        &CosmicBackgroundColor,
-        &CursorColor,
-        &SelectionColor,
-        Option<&SelectedTextColor>,
+        (
+            &CosmicBackgroundColor,
+            &CursorColor,
+            &SelectionColor,
+            Option<&SelectedTextColor>,
+            Option<&InactiveSelectionColor>,
+            Option<&StyledTextColor>,
+        ),
         &CosmicRenderOutput,
         CosmicWidgetSize,
         Option<&ReadOnly>,
+        Has<Disabled>,
         &CosmicTextAlign,
         &CosmicWrap,
+        Option<&CosmicRenderMode>,
+        Option<&ImePreedit>,
+        &mut RenderCache,
     )>,
     mut font_system: ResMut<CosmicFontSystem>,
     mut images: ResMut<Assets<Image>>,
-    mut swash_cache_state: ResMut<SwashCache>,
+    threads: Res<RenderThreads>,
+    mut fonts: Local<GlyphFonts>,
+    rasterizers: Local<Parallel<GlyphRasterizer>>,
 ) {
     for (
         mut editor,
         attrs,
         background_image,
-        fill_color,
-        cursor_color,
-        selection_color,
-        selected_text_color_option,
+        (
+            fill_color,
+            cursor_color,
+            selection_color,
+            selected_text_color_option,
+            inactive_selection_color_opt,
+            styled_text_color_opt,
+        ),
         canvas,
         size,
         readonly_opt,
+        disabled,
         text_align,
         wrap,
+        render_mode,
+        preedit_opt,
+        mut cache,
     ) in query.iter_mut()
     {
+        if render_mode == Some(&CosmicRenderMode::GlyphAtlas) {
+            continue;
+        }
         let font_system = &mut font_system.0;
-        let Ok(render_target_size) = size.logical_size() else {
+        let Some((render_target_size, content)) =
+            fit_buffer(&mut editor, font_system, &size, text_align, wrap)
+        else {
             continue;
         };
 
-        // avoids a panic
-        if render_target_size.x == 0. || render_target_size.y == 0. {
-            debug!(
-                message = "Size of buffer is zero, skipping",
-                // once = "This log only appears once"
-            );
+        // nothing changed since the last time this widget was drawn
+        if !editor.redraw() {
+            continue;
+        }
+
+        let (transformation, clip) = shape_for_drawing(
+            &mut editor,
+            font_system,
+            text_align,
+            render_target_size,
+            content,
+        );
+
+        // find the lines that look different from the last draw
+        let focused = editor.editor().is_some();
+        let cursor_visible = editor
+            .editor()
+            .is_some_and(|editor| editor.cursor_visible && readonly_opt.is_none());
+        let cursor = editor
+            .editor()
+            .filter(|_| cursor_visible && cursor_color.0.alpha() > 0.)
+            .map(|editor| editor.cursor());
+        let selection_drawn = focused
+            || (inactive_selection_color_opt.is_some() && editor.selection() != Selection::None);
+        let selection_bounds = if selection_drawn {
+            editor.with_editor_mut(|editor| editor.selection_bounds())
+        } else {
+            None
+        };
+        let preedit = preedit_opt
+            .zip(editor.get_editor())
+            .and_then(|(preedit, editor)| preedit.overlay(editor, font_system));
+        // the preedit hides the cursor and selection
+        let (selection_bounds, cursor) = match &preedit {
+            Some(preedit) => {
+                for run in preedit.layout_runs() {
+                    fonts.add_run(font_system, &run);
+                }
+                (None, None)
+            }
+            None => (selection_bounds, cursor),
+        };
+        let runs: Vec<DrawnRun> = editor
+            .layout_runs()
+            .map(|run| {
+                // glyphs can't be looked up in the font system while rasterizing
+                fonts.add_run(font_system, &run);
+                DrawnRun::new(&run, selection_bounds, cursor, preedit.as_ref())
+            })
+            .collect();
+
+        let width = render_target_size.x as usize;
+        let height = render_target_size.y as usize;
+        let partial = !cache.full_redraw
+            && background_image.0.is_none()
+            && cache.size == render_target_size.as_uvec2()
+            && cache.offset == transformation.offset;
+        let mut redraw_rows = if partial {
+            cache.changed_rows(&runs, transformation.offset.y, height)
+        } else {
+            vec![true; height]
+        };
+        if !redraw_rows.contains(&true) {
+            editor.set_redraw(false);
             continue;
         }
 
         // Draw background
-        let mut pixels = vec![0; render_target_size.x as usize * render_target_size.y as usize * 4];
-        if let Some(bg_image) = background_image.0.clone() {
-            if let Some(image) = images.get(&bg_image) {
-                let mut dynamic_image = image.clone().try_into_dynamic().unwrap();
-                if image.size() != render_target_size.as_uvec2() {
-                    dynamic_image = dynamic_image.resize_to_fill(
-                        render_target_size.x as u32,
-                        render_target_size.y as u32,
-                        FilterType::Triangle,
+        let previous_pixels = partial
+            .then(|| {
+                images
+                    .get_mut(&canvas.0)
+                    .and_then(|image| image.data.take())
+            })
+            .flatten()
+            .filter(|pixels| pixels.len() == width * height * 4);
+        let pixels = match previous_pixels {
+            // only clear the rows that are drawn again
+            Some(mut pixels) => {
+                for (row, _) in redraw_rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, redraw)| **redraw)
+                {
+                    fill_pixels(
+                        &mut pixels[row * width * 4..(row + 1) * width * 4],
+                        fill_color.0.to_cosmic(),
                     );
                 }
-                for (i, (_, _, rgba)) in dynamic_image.pixels().enumerate() {
-                    if let Some(p) = pixels.get_mut(i * 4..(i + 1) * 4) {
-                        p[0] = rgba[0];
-                        p[1] = rgba[1];
-                        p[2] = rgba[2];
-                        p[3] = rgba[3];
-                    }
-                }
+                pixels
             }
-        } else {
-            let bg = fill_color.0.to_cosmic();
-            for pixel in pixels.chunks_exact_mut(4) {
-                pixel[0] = bg.r(); // Red component
-                pixel[1] = bg.g(); // Green component
-                pixel[2] = bg.b(); // Blue component
-                pixel[3] = bg.a(); // Alpha component
+            None => {
+                redraw_rows.fill(true);
+                draw_background(
+                    &images,
+                    background_image,
+                    fill_color,
+                    render_target_size.as_uvec2(),
+                )
+            }
+        };
+
+        // glyphs entirely outside of the redrawn rows are skipped
+        let first_row = redraw_rows.iter().position(|redraw| *redraw).unwrap_or(0) as i32;
+        let last_row = redraw_rows.iter().rposition(|redraw| *redraw).unwrap_or(0) as i32;
+        let visible = clip.intersect(IRect::new(0, first_row, width as i32, last_row + 1));
+        let visible = IRect::from_corners(
+            transformation
+                .widget_topleft_to_buffer_topleft(visible.min.as_vec2())
+                .floor()
+                .as_ivec2(),
+            transformation
+                .widget_topleft_to_buffer_topleft(visible.max.as_vec2())
+                .ceil()
+                .as_ivec2(),
+        );
+
+        cache.job = Some(RenderJob {
+            pixels,
+            size: render_target_size.as_uvec2(),
+            redraw_rows,
+            transformation,
+            clip,
+            visible,
+            colors: RenderColors::new(
+                attrs,
+                cursor_color,
+                selection_color,
+                selected_text_color_option,
+                inactive_selection_color_opt,
+                styled_text_color_opt,
+                cursor_visible,
+            ),
+            disabled,
+            preedit,
+            runs,
+        });
+    }
+
+    // every thread rasterizes with its own glyph cache
+    let fonts = &*fonts;
+    match *threads {
+        RenderThreads::TaskPool => query.par_iter_mut().for_each_init(
+            || rasterizers.borrow_local_mut(),
+            |rasterizer, (mut editor, .., mut cache)| {
+                if let Some(job) = &mut cache.job {
+                    job.rasterize(&mut editor, fonts, rasterizer);
+                }
+            },
+        ),
+        RenderThreads::MainThread => {
+            let mut rasterizer = rasterizers.borrow_local_mut();
+            for (mut editor, .., mut cache) in query.iter_mut() {
+                if let Some(job) = &mut cache.job {
+                    job.rasterize(&mut editor, fonts, &mut rasterizer);
+                }
             }
         }
+    }
 
-        let font_color = attrs
-            .0
-            .color_opt
-            .unwrap_or(cosmic_text::Color::rgb(0, 0, 0));
+    for (mut editor, _, _, _, canvas, .., mut cache) in query.iter_mut() {
+        let Some(job) = cache.job.take() else {
+            continue;
+        };
+        editor.set_redraw(false);
+        cache.full_redraw = false;
+        cache.size = job.size;
+        cache.offset = job.transformation.offset;
+        cache.runs = job.runs;
+        upload_pixels(&mut images, canvas, job.pixels, job.size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // compute y-offset
-        let buffer_size = editor.borrow_with(font_system).expected_size();
+    #[test]
+    fn padding_offsets_buffer() {
+        let size = Vec2::new(100., 50.);
+        let content = Rect::from_corners(Vec2::new(10., 5.), Vec2::new(90., 45.));
         let transformation = WidgetBufferCoordTransformation::new(
-            text_align.vertical,
-            render_target_size,
-            buffer_size,
+            VerticalAlign::Center,
+            size,
+            content,
+            Vec2::new(80., 20.),
         );
 
-        // let mut actually_rendered_max = IVec2::ZERO;
-        // let mut actually_rendered_min = IVec2::new(i32::MAX, i32::MAX);
-        let draw_closure = |x, y, w, h, color| {
-            for row in 0..h as i32 {
-                for col in 0..w as i32 {
-                    let buffer_coord = IVec2::new(x + col, y + row);
-                    // actually_rendered_max = actually_rendered_max.max(buffer_coord);
-                    // actually_rendered_min = actually_rendered_min.min(buffer_coord);
+        let widget = transformation.buffer_to_widget(Vec2::ZERO);
+        assert_eq!(widget, Vec2::new(10., 15.));
+        assert_eq!(
+            transformation.widget_topleft_to_buffer_topleft(widget),
+            Vec2::ZERO
+        );
+        // centered on the widget
+        let centered = Vec2::new(widget.x - size.x / 2., size.y / 2. - widget.y);
+        assert_eq!(
+            transformation.widget_origined_to_buffer_topleft(centered),
+            Vec2::ZERO
+        );
+    }
 
-                    // compute padding_top
-                    let widget_coord = transformation
-                        .buffer_to_widget(buffer_coord.as_vec2())
-                        .as_ivec2();
+    #[test]
+    fn only_changed_widgets_are_redrawn() {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlasLayout>()
+            .init_resource::<RenderThreads>()
+            .add_plugins(plugin);
 
-                    // actually draw pixel
-                    draw_pixel(
-                        &mut pixels,
-                        render_target_size.x as i32,
-                        render_target_size.y as i32,
-                        widget_coord.x,
-                        widget_coord.y,
-                        color,
-                    );
-                }
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, cosmic_text::Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let widget = app
+            .world_mut()
+            .spawn((
+                TextEdit2d,
+                buffer,
+                Sprite {
+                    custom_size: Some(Vec2::new(40., 20.)),
+                    ..default()
+                },
+                CosmicBackgroundColor(Color::WHITE),
+            ))
+            .id();
+        let pixels = |app: &App| {
+            let output = app.world().get::<CosmicRenderOutput>(widget).unwrap().id();
+            let images = app.world().resource::<Assets<Image>>();
+            images.get(output).unwrap().data.clone().unwrap()
+        };
+        let clear = |app: &mut App| {
+            let output = app.world().get::<CosmicRenderOutput>(widget).unwrap().id();
+            let mut images = app.world_mut().resource_mut::<Assets<Image>>();
+            if let Some(data) = &mut images.get_mut(output).unwrap().data {
+                data.fill(0);
             }
         };
 
-        // BUG: overflow when using center/right/end aligned infinite wrap
-        editor.set_size(
-            font_system,
-            Some(match wrap {
-                CosmicWrap::Wrap => render_target_size.x,
-                // probably high enough
-                CosmicWrap::InfiniteLine => f32::MAX / 10f32.powi(3),
-            }),
-            Some(render_target_size.y),
-        );
-        if let Some(alignment) = text_align.horizontal {
-            for line in &mut editor.lines {
-                line.set_align(Some(alignment.into()));
-            }
-        }
+        app.update();
+        assert_ne!(pixels(&app)[..4], [0, 0, 0, 0]);
 
-        // Draw glyphs
-        if let Some(editor) = editor.editor() {
-            // todo: optimizations (see below comments)
-            editor.set_redraw(true);
-            if !editor.redraw() {
-                continue;
-            }
+        // nothing changed, so the cleared image stays untouched
+        clear(&mut app);
+        app.update();
+        assert_eq!(pixels(&app)[..4], [0, 0, 0, 0]);
 
-            let cursor_color = cursor_color.0;
-            let cursor_opacity = if editor.cursor_visible && readonly_opt.is_none() {
-                cursor_color.alpha()
-            } else {
-                0.
-            };
+        app.world_mut()
+            .entity_mut(widget)
+            .insert(CosmicBackgroundColor(Color::BLACK));
+        app.update();
+        assert_eq!(pixels(&app)[..4], [0, 0, 0, 255]);
+    }
 
-            let cursor_color = cursor_color.with_alpha(cursor_opacity).to_cosmic();
-
-            let selection_color = selection_color.0.to_cosmic();
-
-            let selected_text_color = selected_text_color_option
-                .map(|selected_text_color| selected_text_color.0.to_cosmic())
-                .unwrap_or(font_color);
-
-            // try to fix annoying scroll behaviour
-            // by only allowing vertical scrolling if the buffer is actually larger than the canvas
-            // let mut scroll = editor.with_buffer(|b| b.scroll());
-            // if buffer_size.y + 10.0 < render_target_size.y {
-            //     trace!(
-            //         message = "Ignoring vertical scroll as buffer is smaller than canvas",
-            //         ?buffer_size.y,
-            //         ?render_target_size.y
-            //     );
-            //     scroll.vertical = 0.0;
-            // }
-            // editor.with_buffer_mut(|b| b.set_scroll(scroll));
-
-            // let new_buffer_size = editor.expected_size();
-
-            let mut editor = editor.borrow_with(font_system);
-            editor.shape_as_needed(false);
-            editor.draw(
-                &mut swash_cache_state.0,
-                font_color,
-                cursor_color,
-                selection_color,
-                selected_text_color,
-                draw_closure,
+    #[test]
+    fn fitting_unchanged_widgets_keeps_them_unchanged() {
+        #[derive(Resource, Default)]
+        struct Changes(usize);
+
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlasLayout>()
+            .init_resource::<RenderThreads>()
+            .init_resource::<Changes>()
+            .add_plugins(plugin)
+            .add_systems(
+                Last,
+                |widgets: Query<(), Changed<CosmicEditBuffer>>, mut changes: ResMut<Changes>| {
+                    changes.0 += widgets.iter().count();
+                },
             );
 
-            // if coord calculations seem to be buggy, this code may help you to debug
-            // let actually_rendered_buffer_size = actually_rendered_max - actually_rendered_min;
-            // trace!(
-            //     ?buffer_size,
-            //     ?new_buffer_size,
-            //     ?actually_rendered_buffer_size
-            // );
-            // transformation.debug_top_padding();
-            // debug check only
-            // if (new_buffer_size.as_ivec2() - actually_rendered_buffer_size)
-            //     .as_vec2()
-            //     .length()
-            //     > 5.0
-            // {
-            //     warn_once!(
-            //         message = "Calculations of buffer sizes are off by a significant amount",
-            //         note = "This is likely an internal bug with bevy_cosmic_edit"
-            //     );
-            // }
-
-            // PERF: Read all possible render-input changes and only redraw if necessary
-            // editor.set_redraw(false);
-        } else {
-            // todo: performance optimizations (see comments above/below)
-            editor.set_redraw(true);
-            if !editor.redraw() {
-                continue;
-            }
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, cosmic_text::Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        app.world_mut().spawn((
+            TextEdit2d,
+            buffer,
+            CosmicTextAlign::center(),
+            Sprite {
+                custom_size: Some(Vec2::new(40., 20.)),
+                ..default()
+            },
+        ));
 
-            // editor.borrow_with(font_system).compute_everything();
-            editor.shape_until_scroll(font_system, false);
-            editor.draw(
-                font_system,
-                &mut swash_cache_state.0,
-                font_color,
-                draw_closure,
-            );
+        app.update();
+        assert_eq!(app.world().resource::<Changes>().0, 1);
+        app.update();
+        app.update();
+        assert_eq!(app.world().resource::<Changes>().0, 1);
+    }
 
-            // PERF: Read all possible render-input changes and only redraw if necessary
-            // buffer.set_redraw(false);
-        }
+    #[test]
+    fn task_pool_renders_like_main_thread() {
+        let render = |threads: RenderThreads| {
+            let mut app = App::new();
+            app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+                .init_asset::<Image>()
+                .init_asset::<TextureAtlasLayout>()
+                .insert_resource(threads)
+                .add_plugins(plugin);
 
-        if let Some(prev_image) = images.get_mut(&canvas.0) {
-            if let Some(ref mut data) = prev_image.data {
-                data.clear();
-                // Updates the stored asset image with the computed pixels
-                data.extend_from_slice(pixels.as_slice());
-            }
-            prev_image.resize(Extent3d {
-                width: render_target_size.x as u32,
-                height: render_target_size.y as u32,
-                depth_or_array_layers: 1,
-            });
-        }
+            let mut db = cosmic_text::fontdb::Database::new();
+            db.load_font_data(include_bytes!("font/FiraMono-Regular-subset.ttf").to_vec());
+            let mut font_system =
+                cosmic_text::FontSystem::new_with_locale_and_db("en-US".into(), db);
+            let attrs = cosmic_text::Attrs::new().family(cosmic_text::Family::Name("Fira Mono"));
+            let buffers: Vec<_> = (0..8)
+                .map(|i| {
+                    CosmicEditBuffer::new(&mut font_system, cosmic_text::Metrics::new(20., 20.))
+                        .with_text(&mut font_system, &format!("Widget {i}\nabc"), attrs.clone())
+                })
+                .collect();
+            app.insert_resource(CosmicFontSystem(font_system));
+            let widgets: Vec<Entity> = buffers
+                .into_iter()
+                .map(|buffer| {
+                    app.world_mut()
+                        .spawn((
+                            TextEdit2d,
+                            buffer,
+                            Sprite {
+                                custom_size: Some(Vec2::new(120., 50.)),
+                                ..default()
+                            },
+                            CosmicBackgroundColor(Color::WHITE),
+                        ))
+                        .id()
+                })
+                .collect();
+
+            app.update();
+            let images = app.world().resource::<Assets<Image>>();
+            widgets
+                .iter()
+                .map(|widget| {
+                    let output = app.world().get::<CosmicRenderOutput>(*widget).unwrap();
+                    images.get(output.id()).unwrap().data.clone().unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let main_thread = render(RenderThreads::MainThread);
+        // text was drawn over the background
+        let background = &main_thread[0][..4];
+        assert!(main_thread[0]
+            .chunks_exact(4)
+            .any(|pixel| pixel != background));
+        assert_eq!(render(RenderThreads::TaskPool), main_thread);
+    }
+
+    #[test]
+    fn only_changed_runs_are_redrawn() {
+        let run = |top: f32, signature| DrawnRun {
+            top,
+            bottom: top + 10.,
+            signature,
+        };
+        let cache = RenderCache {
+            runs: vec![run(0., 1), run(10., 2), run(20., 3)],
+            ..default()
+        };
+        let redrawn = |rows: Vec<bool>| {
+            rows.iter()
+                .enumerate()
+                .filter(|(_, redraw)| **redraw)
+                .map(|(row, _)| row)
+                .collect::<Vec<_>>()
+        };
+
+        let rows = cache.changed_rows(&[run(0., 1), run(10., 4), run(20., 3)], 5., 40);
+        assert_eq!(redrawn(rows), (15..25).collect::<Vec<_>>());
+
+        // removed runs are cleared
+        let rows = cache.changed_rows(&[run(0., 1), run(10., 2)], 5., 40);
+        assert_eq!(redrawn(rows), (25..35).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn blends_onto_opaque_background() {
+        let mut pixels = [0, 0, 255, 255];
+        draw_pixel(
+            &mut pixels,
+            1,
+            1,
+            0,
+            0,
+            cosmic_text::Color::rgba(255, 0, 0, 128),
+        );
+        assert_eq!(pixels, [128, 0, 127, 255]);
+
+        draw_pixel(&mut pixels, 1, 1, 0, 0, cosmic_text::Color::rgb(0, 255, 0));
+        assert_eq!(pixels, [0, 255, 0, 255]);
     }
 }
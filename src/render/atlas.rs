@@ -0,0 +1,588 @@
+//! [`CosmicRenderMode::GlyphAtlas`]: glyphs are rasterized once into shared atlas textures,
+//! and widgets are drawn as quads showing them
+
+use super::rasterize::{swash_image, GlyphFonts};
+use super::{
+    disabled_color, draw_background, draw_text, fit_buffer, glyph_color, shape_for_drawing,
+    upload_pixels, DrawnRun, RenderCache, RenderColors,
+};
+use crate::input::ime::{ImePreedit, PreeditOverlay};
+use crate::render_implementations::{CosmicWidgetSize, Quad, QuadTarget};
+use crate::{cosmic_edit::*, prelude::*, style::StyledTextColor};
+use bevy::asset::RenderAssetUsages;
+use bevy::platform::collections::HashMap;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::text::{FontAtlas, FontSmoothing};
+use cosmic_text::{CacheKey, Selection, SwashContent};
+use swash::scale::ScaleContext;
+
+/// Every glyph drawn by widgets in [`CosmicRenderMode::GlyphAtlas`], shared between them
+#[derive(Resource, Default)]
+pub(super) struct GlyphAtlas {
+    fonts: GlyphFonts,
+    context: ScaleContext,
+    atlases: Vec<FontAtlas>,
+    /// `None` for glyphs without any pixels
+    glyphs: HashMap<CacheKey, Option<AtlasGlyph>>,
+}
+
+/// Where a glyph is in the [`GlyphAtlas`]
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+    atlas: usize,
+    index: usize,
+    /// From the pen position to the top left of the glyph, y up
+    offset: IVec2,
+    size: UVec2,
+    /// Color glyphs like emoji keep their own colors
+    color: bool,
+}
+
+impl GlyphAtlas {
+    /// Rasterizes the glyph into an atlas the first time it is drawn
+    fn glyph(
+        &mut self,
+        images: &mut Assets<Image>,
+        layouts: &mut Assets<TextureAtlasLayout>,
+        cache_key: CacheKey,
+    ) -> Option<AtlasGlyph> {
+        if let Some(glyph) = self.glyphs.get(&cache_key) {
+            return *glyph;
+        }
+        let glyph = self.add_glyph(images, layouts, cache_key);
+        self.glyphs.insert(cache_key, glyph);
+        glyph
+    }
+
+    fn add_glyph(
+        &mut self,
+        images: &mut Assets<Image>,
+        layouts: &mut Assets<TextureAtlasLayout>,
+        cache_key: CacheKey,
+    ) -> Option<AtlasGlyph> {
+        let image = swash_image(&self.fonts, &mut self.context, cache_key)?;
+        let size = UVec2::new(image.placement.width, image.placement.height);
+        if size.x == 0 || size.y == 0 {
+            return None;
+        }
+        // masks are tinted with the text color, like bevy's text
+        let (data, color) = match image.content {
+            SwashContent::Mask => (
+                image
+                    .data
+                    .iter()
+                    .flat_map(|alpha| [255, 255, 255, *alpha])
+                    .collect(),
+                false,
+            ),
+            SwashContent::Color => (image.data, true),
+            SwashContent::SubpixelMask => {
+                warn_once!("Subpixel glyph masks are not supported");
+                return None;
+            }
+        };
+        let texture = Image::new(
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::MAIN_WORLD,
+        );
+        let offset = IVec2::new(image.placement.left, image.placement.top);
+
+        let atlas = match self.atlases.iter_mut().position(|atlas| {
+            atlas
+                .add_glyph(images, layouts, cache_key, &texture, offset)
+                .is_ok()
+        }) {
+            Some(atlas) => atlas,
+            // every atlas is full, start another one the way bevy's text does
+            None => {
+                let side = (1u32 << (32 - size.max_element().leading_zeros())).max(512);
+                let mut atlas = FontAtlas::new(
+                    images,
+                    layouts,
+                    UVec2::splat(side),
+                    FontSmoothing::AntiAliased,
+                );
+                if let Err(err) = atlas.add_glyph(images, layouts, cache_key, &texture, offset) {
+                    warn!(message = "Failed to add a glyph to the atlas", ?err);
+                    return None;
+                }
+                self.atlases.push(atlas);
+                self.atlases.len() - 1
+            }
+        };
+        let index = self.atlases[atlas].get_glyph_index(cache_key)?.glyph_index;
+        Some(AtlasGlyph {
+            atlas,
+            index,
+            offset,
+            size,
+            color,
+        })
+    }
+}
+
+/// A rectangle or glyph in buffer coordinates, before it is placed in the widget
+struct BufferQuad {
+    min: IVec2,
+    size: UVec2,
+    color: cosmic_text::Color,
+    glyph: Option<AtlasGlyph>,
+    /// The baseline glyphs were drawn on, which tells their run
+    baseline: i32,
+}
+
+/// Collects the quads a widget is drawn with, instead of drawing pixels
+struct QuadRenderer<'a> {
+    atlas: &'a mut GlyphAtlas,
+    images: &'a mut Assets<Image>,
+    layouts: &'a mut Assets<TextureAtlasLayout>,
+    /// Replaces glyph colors, except for text drawn in `selected_text_color`
+    text_color: Option<cosmic_text::Color>,
+    selected_text_color: Option<cosmic_text::Color>,
+    quads: Vec<BufferQuad>,
+}
+
+impl cosmic_text::Renderer for QuadRenderer<'_> {
+    fn rectangle(&mut self, x: i32, y: i32, w: u32, h: u32, color: cosmic_text::Color) {
+        // e.g. the hidden cursor
+        if color.a() == 0 {
+            return;
+        }
+        self.quads.push(BufferQuad {
+            min: IVec2::new(x, y),
+            size: UVec2::new(w, h),
+            color,
+            glyph: None,
+            baseline: y,
+        });
+    }
+
+    fn glyph(&mut self, physical_glyph: cosmic_text::PhysicalGlyph, color: cosmic_text::Color) {
+        let Some(glyph) = self
+            .atlas
+            .glyph(self.images, self.layouts, physical_glyph.cache_key)
+        else {
+            return;
+        };
+        // like the texture mode, glyphs ignore the alpha of the text color
+        let color = if glyph.color {
+            cosmic_text::Color::rgb(255, 255, 255)
+        } else {
+            let [r, g, b, _] =
+                glyph_color(color, self.text_color, self.selected_text_color).as_rgba();
+            cosmic_text::Color::rgb(r, g, b)
+        };
+        self.quads.push(BufferQuad {
+            min: IVec2::new(
+                physical_glyph.x + glyph.offset.x,
+                physical_glyph.y - glyph.offset.y,
+            ),
+            size: glyph.size,
+            color,
+            glyph: Some(glyph),
+            baseline: physical_glyph.y,
+        });
+    }
+}
+
+/// The child entities a widget in [`CosmicRenderMode::GlyphAtlas`] is drawn with.
+///
+/// The glyphs of runs that look the same are kept as they are between redraws,
+/// so cursor blinks only update the cursor and selection
+#[derive(Component, Default)]
+pub(crate) struct GlyphQuads {
+    /// The glyphs of each run, by [`DrawnRun::signature`]
+    runs: HashMap<u64, Vec<Entity>>,
+    /// Everything else, like the cursor and selection
+    rects: Vec<Entity>,
+}
+
+impl GlyphQuads {
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.runs.values().flatten().chain(&self.rects).copied()
+    }
+}
+
+/// Draws widgets in [`CosmicRenderMode::GlyphAtlas`] as quads over their background.
+///
+/// Like [`render_texture`](super::render_texture), only widgets with [`Buffer::redraw`] set
+/// are drawn. Their image is only drawn again when the background changes
+pub(super) fn render_glyph_quads(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        EditorBuffer,
+        &DefaultAttrs,
+        &CosmicBackgroundImage,
+        (
+            &CosmicBackgroundColor,
+            &CursorColor,
+            &SelectionColor,
+            Option<&SelectedTextColor>,
+            Option<&InactiveSelectionColor>,
+            Option<&StyledTextColor>,
+        ),
+        &CosmicRenderOutput,
+        (CosmicWidgetSize, QuadTarget),
+        Option<&ReadOnly>,
+        Has<Disabled>,
+        (&CosmicTextAlign, &CosmicWrap),
+        Option<&CosmicRenderMode>,
+        Option<&ImePreedit>,
+        &mut RenderCache,
+        Option<&mut GlyphQuads>,
+    )>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut atlas: ResMut<GlyphAtlas>,
+    mut images: ResMut<Assets<Image>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    for (
+        widget,
+        mut editor,
+        attrs,
+        background_image,
+        (
+            fill_color,
+            cursor_color,
+            selection_color,
+            selected_text_color_option,
+            inactive_selection_color_opt,
+            styled_text_color_opt,
+        ),
+        canvas,
+        (size, target),
+        readonly_opt,
+        disabled,
+        (text_align, wrap),
+        render_mode,
+        preedit_opt,
+        mut cache,
+        mut glyph_quads,
+    ) in query.iter_mut()
+    {
+        if render_mode != Some(&CosmicRenderMode::GlyphAtlas) {
+            // the widget is rendered to a texture again
+            if let Some(glyph_quads) = glyph_quads {
+                for entity in glyph_quads.entities() {
+                    commands.entity(entity).despawn();
+                }
+                commands.entity(widget).remove::<GlyphQuads>();
+            }
+            continue;
+        }
+        let font_system = &mut font_system.0;
+        let Some((render_target_size, content)) =
+            fit_buffer(&mut editor, font_system, &size, text_align, wrap)
+        else {
+            continue;
+        };
+
+        // nothing changed since the last time this widget was drawn
+        if !editor.redraw() {
+            continue;
+        }
+
+        let (transformation, clip) = shape_for_drawing(
+            &mut editor,
+            font_system,
+            text_align,
+            render_target_size,
+            content,
+        );
+        let preedit = preedit_opt
+            .zip(editor.get_editor())
+            .and_then(|(preedit, editor)| preedit.overlay(editor, font_system));
+        for run in editor
+            .layout_runs()
+            .chain(preedit.iter().flat_map(PreeditOverlay::layout_runs))
+        {
+            atlas.fonts.add_run(font_system, &run);
+        }
+
+        // the text isn't part of the image, so it only changes with the background
+        let physical_size = render_target_size.as_uvec2();
+        // glyphs of unchanged runs stay where they are
+        let keep_runs = !cache.full_redraw
+            && cache.size == physical_size
+            && cache.offset == transformation.offset;
+        if cache.full_redraw || cache.size != physical_size {
+            let pixels = draw_background(&images, background_image, fill_color, physical_size);
+            upload_pixels(&mut images, canvas, pixels, physical_size);
+            cache.full_redraw = false;
+            cache.size = physical_size;
+        }
+        cache.offset = transformation.offset;
+
+        let focused = editor.editor().is_some();
+        let cursor_visible = editor
+            .editor()
+            .is_some_and(|editor| editor.cursor_visible && readonly_opt.is_none());
+        let colors = RenderColors::new(
+            attrs,
+            cursor_color,
+            selection_color,
+            selected_text_color_option,
+            inactive_selection_color_opt,
+            styled_text_color_opt,
+            cursor_visible,
+        );
+
+        // the cursor is a quad of its own, only the selection changes how glyphs look
+        let selection_drawn = focused
+            || (inactive_selection_color_opt.is_some() && editor.selection() != Selection::None);
+        let selection_bounds = if selection_drawn && preedit.is_none() {
+            editor.with_editor_mut(|editor| editor.selection_bounds())
+        } else {
+            None
+        };
+        let runs: Vec<(f32, f32, u64)> = editor
+            .layout_runs()
+            .map(|run| {
+                let drawn = DrawnRun::new(&run, selection_bounds, None, preedit.as_ref());
+                (
+                    run.line_top,
+                    run.line_top + run.line_height,
+                    drawn.signature,
+                )
+            })
+            .collect();
+
+        let mut renderer = QuadRenderer {
+            atlas: &mut atlas,
+            images: &mut images,
+            layouts: &mut layouts,
+            text_color: colors.text_override,
+            selected_text_color: colors.selected_text_override(focused),
+            quads: Vec::new(),
+        };
+        draw_text(&mut editor, &mut renderer, &colors, preedit.as_ref());
+        let mut run_quads: Vec<Vec<BufferQuad>> = runs.iter().map(|_| Vec::new()).collect();
+        let mut rect_quads = Vec::new();
+        for quad in renderer.quads {
+            let baseline = quad.baseline as f32;
+            let run = quad.glyph.and_then(|_| {
+                runs.iter()
+                    .position(|(top, bottom, _)| (*top..=*bottom).contains(&baseline))
+            });
+            match run {
+                Some(run) => run_quads[run].push(quad),
+                None => rect_quads.push(quad),
+            }
+        }
+
+        let scale_factor = size.scale_factor();
+        let widget_size = render_target_size / scale_factor;
+        // scrolled text doesn't spill into the padding
+        let clip = clip.as_rect();
+        let place = |quad: &BufferQuad| {
+            let min = transformation.buffer_to_widget(quad.min.as_vec2()).floor();
+            let rect = Rect::from_corners(min, min + quad.size.as_vec2());
+            let visible = rect.intersect(clip);
+            if visible.is_empty() {
+                return None;
+            }
+            let color = if disabled {
+                disabled_color(quad.color)
+            } else {
+                quad.color
+            };
+            let [r, g, b, a] = color.as_rgba();
+            let texture = quad.glyph.map(|glyph| {
+                let font_atlas = &atlas.atlases[glyph.atlas];
+                (
+                    font_atlas.texture.clone(),
+                    TextureAtlas {
+                        layout: font_atlas.texture_atlas.clone(),
+                        index: glyph.index,
+                    },
+                    // one texel per physical pixel
+                    Rect::from_corners(visible.min - rect.min, visible.max - rect.min),
+                )
+            });
+            Some(Quad {
+                rect: Rect::from_corners(visible.min / scale_factor, visible.max / scale_factor),
+                color: Color::srgba_u8(r, g, b, a),
+                texture,
+            })
+        };
+
+        let mut previous = glyph_quads
+            .as_deref_mut()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        let mut kept: HashMap<u64, Vec<Entity>> = HashMap::default();
+        if keep_runs {
+            for (.., signature) in &runs {
+                if let Some((signature, entities)) = previous.runs.remove_entry(signature) {
+                    kept.insert(signature, entities);
+                }
+            }
+        }
+        // the quads of changed runs are drawn with the entities of the old ones
+        let mut unused = previous.entities().collect::<Vec<_>>().into_iter();
+        let mut draw = |quad: Quad| {
+            let entity = unused
+                .next()
+                .unwrap_or_else(|| commands.spawn((ChildOf(widget), Pickable::IGNORE)).id());
+            if let Err(err) = target.insert_quad(&mut commands.entity(entity), quad, widget_size) {
+                debug!(message = "Failed to draw a glyph quad", ?err);
+            }
+            entity
+        };
+        let mut drawn = GlyphQuads {
+            runs: HashMap::default(),
+            rects: rect_quads.iter().filter_map(place).map(&mut draw).collect(),
+        };
+        for ((.., signature), quads) in runs.iter().zip(run_quads) {
+            let entities = kept
+                .remove(signature)
+                .unwrap_or_else(|| quads.iter().filter_map(place).map(&mut draw).collect());
+            drawn.runs.insert(*signature, entities);
+        }
+        for entity in unused {
+            commands.entity(entity).despawn();
+        }
+
+        match glyph_quads {
+            Some(mut glyph_quads) => *glyph_quads = drawn,
+            None => {
+                commands.entity(widget).insert(drawn);
+            }
+        }
+        editor.set_redraw(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RenderThreads;
+    use bevy::sprite::Anchor;
+
+    #[test]
+    fn glyph_atlas_draws_text_as_quads() {
+        let mut app = App::new();
+        app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlasLayout>()
+            .init_resource::<RenderThreads>()
+            .add_plugins(super::super::plugin);
+
+        let mut db = cosmic_text::fontdb::Database::new();
+        db.load_font_data(include_bytes!("../font/FiraMono-Regular-subset.ttf").to_vec());
+        let mut font_system = cosmic_text::FontSystem::new_with_locale_and_db("en-US".into(), db);
+        let attrs = cosmic_text::Attrs::new().family(cosmic_text::Family::Name("Fira Mono"));
+        let buffer = CosmicEditBuffer::new(&mut font_system, cosmic_text::Metrics::new(20., 20.))
+            .with_text(&mut font_system, "abc", attrs.clone());
+        app.insert_resource(CosmicFontSystem(font_system));
+        let widget = app
+            .world_mut()
+            .spawn((
+                TextEdit2d,
+                buffer,
+                Sprite {
+                    custom_size: Some(Vec2::new(120., 50.)),
+                    ..default()
+                },
+                Anchor::BOTTOM_RIGHT,
+                CosmicBackgroundColor(Color::WHITE),
+                CosmicTextAlign::top_left(),
+                CosmicRenderMode::GlyphAtlas,
+            ))
+            .id();
+        let pixels = |app: &App| {
+            let output = app.world().get::<CosmicRenderOutput>(widget).unwrap().id();
+            let images = app.world().resource::<Assets<Image>>();
+            images.get(output).unwrap().data.clone().unwrap()
+        };
+        let quads = |app: &App| {
+            let glyph_quads = app.world().get::<GlyphQuads>(widget).unwrap();
+            glyph_quads.entities().collect::<Vec<_>>()
+        };
+
+        app.update();
+        // one quad per glyph, the image only has the background
+        let first_line = quads(&app);
+        assert_eq!(first_line.len(), 3);
+        // the corners of the quads, from the top left of the widget
+        let mut min = Vec2::MAX;
+        let mut max = Vec2::MIN;
+        for quad in &first_line {
+            let sprite = app.world().get::<Sprite>(*quad).unwrap();
+            assert!(sprite.texture_atlas.is_some());
+            assert_eq!(app.world().get::<ChildOf>(*quad).unwrap().parent(), widget);
+            // the widget is anchored at its bottom right corner
+            let center = app.world().get::<Transform>(*quad).unwrap().translation;
+            let center = Vec2::new(center.x + 120., 50. - center.y);
+            let half_size = sprite.custom_size.unwrap() / 2.;
+            min = min.min(center - half_size);
+            max = max.max(center + half_size);
+        }
+        let background = pixels(&app);
+        assert!(background
+            .chunks_exact(4)
+            .all(|pixel| pixel == &background[..4]));
+
+        // unchanged lines keep their quads, e.g. while typing on the next line
+        app.world_mut()
+            .entity_mut(first_line[0])
+            .get_mut::<Sprite>()
+            .unwrap()
+            .color = Color::BLACK;
+        app.world_mut()
+            .resource_scope(|world, mut font_system: Mut<CosmicFontSystem>| {
+                world.get_mut::<CosmicEditBuffer>(widget).unwrap().set_text(
+                    &mut font_system,
+                    "abc\ndef",
+                    attrs.clone(),
+                );
+            });
+        app.update();
+        let both_lines = quads(&app);
+        assert_eq!(both_lines.len(), 6);
+        assert!(first_line.iter().all(|quad| both_lines.contains(quad)));
+        assert_eq!(
+            app.world().get::<Sprite>(first_line[0]).unwrap().color,
+            Color::BLACK
+        );
+
+        // the texture draws the glyphs where the quads were
+        app.world_mut()
+            .entity_mut(widget)
+            .insert(CosmicRenderMode::Texture);
+        app.world_mut()
+            .resource_scope(|world, mut font_system: Mut<CosmicFontSystem>| {
+                world.get_mut::<CosmicEditBuffer>(widget).unwrap().set_text(
+                    &mut font_system,
+                    "abc",
+                    attrs,
+                );
+            });
+        app.update();
+        assert!(app.world().get::<GlyphQuads>(widget).is_none());
+        assert!(both_lines
+            .iter()
+            .all(|quad| app.world().get_entity(*quad).is_err()));
+        let text = pixels(&app);
+        assert_ne!(text, background);
+        let mut ink_min = Vec2::MAX;
+        let mut ink_max = Vec2::MIN;
+        for (i, pixel) in text.chunks_exact(4).enumerate() {
+            if pixel != &background[..4] {
+                let pixel = Vec2::new((i % 120) as f32, (i / 120) as f32);
+                ink_min = ink_min.min(pixel);
+                ink_max = ink_max.max(pixel + 1.);
+            }
+        }
+        assert!(ink_min.abs_diff_eq(min, 1.), "{ink_min} != {min}");
+        assert!(ink_max.abs_diff_eq(max, 1.), "{ink_max} != {max}");
+    }
+}
@@ -0,0 +1,145 @@
+//! Glyph rasterization without the [`CosmicFontSystem`], so widgets can be rasterized
+//! on any thread, see [`RenderThreads`]
+
+use crate::prelude::*;
+use bevy::platform::collections::HashMap;
+use cosmic_text::{fontdb, CacheKey, CacheKeyFlags, Font, LayoutRun, SwashContent, SwashImage};
+use std::sync::Arc;
+use swash::scale::{Render, ScaleContext, Source, StrikeWith};
+use swash::zeno::{Angle, Format, Transform, Vector};
+
+/// The fonts of every glyph that was drawn, looked up in the [`CosmicFontSystem`]
+/// on the main thread
+#[derive(Default)]
+pub(super) struct GlyphFonts(HashMap<(fontdb::ID, fontdb::Weight), Option<Arc<Font>>>);
+
+impl GlyphFonts {
+    /// Looks up the fonts of the glyphs in `run`, if they aren't known yet
+    pub fn add_run(&mut self, font_system: &mut cosmic_text::FontSystem, run: &LayoutRun) {
+        for glyph in run.glyphs {
+            self.0
+                .entry((glyph.font_id, glyph.font_weight))
+                .or_insert_with(|| font_system.get_font(glyph.font_id, glyph.font_weight));
+        }
+    }
+
+    fn get(&self, cache_key: CacheKey) -> Option<&Font> {
+        self.0
+            .get(&(cache_key.font_id, cache_key.font_weight))
+            .and_then(Option::as_deref)
+    }
+}
+
+/// Like [`cosmic_text::SwashCache`], but with the fonts from [`GlyphFonts`].
+///
+/// Every thread keeps its own, so they don't need to be shared
+#[derive(Default)]
+pub(super) struct GlyphRasterizer {
+    context: ScaleContext,
+    images: HashMap<CacheKey, Option<SwashImage>>,
+}
+
+impl GlyphRasterizer {
+    /// Rasterizes a glyph the same way as [`cosmic_text::SwashCache::get_image`]
+    pub fn get_image(&mut self, fonts: &GlyphFonts, cache_key: CacheKey) -> &Option<SwashImage> {
+        self.images
+            .entry(cache_key)
+            .or_insert_with(|| swash_image(fonts, &mut self.context, cache_key))
+    }
+
+    /// Enumerates the pixels of a glyph, like [`cosmic_text::SwashCache::with_pixels`]
+    pub fn with_pixels(
+        &mut self,
+        fonts: &GlyphFonts,
+        cache_key: CacheKey,
+        base: cosmic_text::Color,
+        mut f: impl FnMut(i32, i32, cosmic_text::Color),
+    ) {
+        let Some(image) = self.get_image(fonts, cache_key) else {
+            return;
+        };
+        let x = image.placement.left;
+        let y = -image.placement.top;
+        let width = image.placement.width as i32;
+        match image.content {
+            SwashContent::Mask => {
+                for (i, alpha) in image.data.iter().enumerate() {
+                    let i = i as i32;
+                    f(
+                        x + i % width,
+                        y + i / width,
+                        cosmic_text::Color((u32::from(*alpha) << 24) | base.0 & 0xFF_FF_FF),
+                    );
+                }
+            }
+            SwashContent::Color => {
+                for (i, rgba) in image.data.chunks_exact(4).enumerate() {
+                    let i = i as i32;
+                    f(
+                        x + i % width,
+                        y + i / width,
+                        cosmic_text::Color::rgba(rgba[0], rgba[1], rgba[2], rgba[3]),
+                    );
+                }
+            }
+            SwashContent::SubpixelMask => {
+                warn_once!("Subpixel glyph masks are not supported");
+            }
+        }
+    }
+}
+
+/// Same as the private `swash_image` of [`cosmic_text`], so both render the same glyphs.
+///
+/// Copied from `src/swash.rs` of cosmic-text 0.16.0, which uses swash 0.2. Compare it
+/// with that function when updating either crate
+pub(super) fn swash_image(
+    fonts: &GlyphFonts,
+    context: &mut ScaleContext,
+    cache_key: CacheKey,
+) -> Option<SwashImage> {
+    let Some(font) = fonts.get(cache_key) else {
+        warn!(message = "Did not find font", font_id = ?cache_key.font_id);
+        return None;
+    };
+
+    let wght = swash::Tag::from_be_bytes(*b"wght");
+    let variable_width = font.as_swash().variations().find_by_tag(wght);
+
+    let mut scaler = context
+        .builder(font.as_swash())
+        .size(f32::from_bits(cache_key.font_size_bits))
+        .hint(!cache_key.flags.contains(CacheKeyFlags::DISABLE_HINTING));
+    if let Some(variation) = variable_width {
+        scaler = scaler.variations(std::iter::once(swash::Setting {
+            tag: wght,
+            value: f32::from(cache_key.font_weight.0)
+                .clamp(variation.min_value(), variation.max_value()),
+        }));
+    }
+    let mut scaler = scaler.build();
+
+    let offset = if cache_key.flags.contains(CacheKeyFlags::PIXEL_FONT) {
+        Vector::new(
+            cache_key.x_bin.as_float().round() + 1.0,
+            cache_key.y_bin.as_float().round(),
+        )
+    } else {
+        Vector::new(cache_key.x_bin.as_float(), cache_key.y_bin.as_float())
+    };
+
+    Render::new(&[
+        Source::ColorOutline(0),
+        Source::ColorBitmap(StrikeWith::BestFit),
+        Source::Outline,
+    ])
+    .format(Format::Alpha)
+    .offset(offset)
+    .transform(
+        cache_key
+            .flags
+            .contains(CacheKeyFlags::FAKE_ITALIC)
+            .then(|| Transform::skew(Angle::from_degrees(14.0), Angle::from_degrees(0.0))),
+    )
+    .render(&mut scaler, cache_key.glyph_id)
+}
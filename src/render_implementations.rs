@@ -52,6 +52,10 @@ mod error {
 
         SpriteExpectedHitdataPosition,
 
+        /// When projecting a [`SourceType::Sprite`] onto the window
+        /// without an active camera that can see it
+        SpriteExpectedCamera,
+
         UiExpectedCursorPosition,
     }
 
@@ -64,6 +68,7 @@ mod error {
                 Self::SpriteCustomSizeNotSet => write!(f, "Sprite custom size not set"),
                 Self::SpriteUnexpectedNormal => write!(f, "Sprite has unexpected normal"),
                 Self::SpriteExpectedHitdataPosition => write!(f, "Sprite expected hit data position"),
+                Self::SpriteExpectedCamera => write!(f, "Sprite expected a camera"),
                 Self::UiExpectedCursorPosition => write!(f, "UI expected cursor position"),
             }
         }
@@ -87,10 +92,14 @@ pub(crate) use coords::*;
 mod coords;
 pub(crate) use output::*;
 mod output;
+pub(crate) use quads::*;
+mod quads;
 pub(crate) use widget_size::*;
 mod widget_size;
 pub(crate) use scan::*;
 mod scan;
+pub(crate) use window::*;
+mod window;
 
 use crate::prelude::*;
 
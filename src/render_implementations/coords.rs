@@ -1,6 +1,6 @@
 use bevy::ecs::query::QueryData;
 use bevy::picking::backend::HitData;
-use bevy::ui::RelativeCursorPosition;
+use bevy::ui::{RelativeCursorPosition, UiGlobalTransform};
 use render_implementations::prelude::*;
 
 use crate::render::WidgetBufferCoordTransformation;
@@ -16,6 +16,8 @@ pub(crate) struct RelativeQuery {
 
     sprite_global_transform: &'static GlobalTransform,
     ui_cursor_position: Option<&'static RelativeCursorPosition>,
+    ui_global_transform: Option<&'static UiGlobalTransform>,
+    ui_node: Option<&'static ComputedNode>,
 }
 
 impl<'w, 's> std::ops::Deref for RelativeQueryItem<'w, 's> {
@@ -48,12 +50,14 @@ impl RelativeQueryItem<'_, '_> {
                 let position_transform =
                     GlobalTransform::from(Transform::from_translation(world_position));
                 let relative_transform = position_transform.reparented_to(sprite_global_transform);
-                let relative_position = relative_transform.translation.xy();
+                let relative_position =
+                    relative_transform.translation.xy() * widget_size.scale_factor();
 
-                let render_target_size = widget_size.logical_size()?;
+                let render_target_size = widget_size.physical_size()?;
                 let transformation = WidgetBufferCoordTransformation::new(
                     text_align.vertical,
                     render_target_size,
+                    widget_size.physical_content_rect()?,
                     buffer_size,
                 );
                 // .xy swizzle depends on normal vector being perfectly out of screen
@@ -76,12 +80,13 @@ impl RelativeQueryItem<'_, '_> {
                     .normalized
                     .ok_or_else(|| Into::<bevy::ecs::error::BevyError>::into(RenderTargetError::UiExpectedCursorPosition))?;
 
-                let widget_size = widget_size.logical_size()?;
-                let relative_position = cursor_position_normalized * widget_size;
+                let render_target_size = widget_size.physical_size()?;
+                let relative_position = cursor_position_normalized * render_target_size;
 
                 let transformation = WidgetBufferCoordTransformation::new(
                     text_align.vertical,
-                    widget_size,
+                    render_target_size,
+                    widget_size.physical_content_rect()?,
                     buffer_size,
                 );
 
@@ -92,4 +97,70 @@ impl RelativeQueryItem<'_, '_> {
             }
         }
     }
+
+    /// The inverse of [`Self::compute_buffer_coord`]: where a buffer coordinate
+    /// ends up in the window, in logical pixels from the top left.
+    ///
+    /// [`SourceType::Sprite`] needs a `camera` to project the widget onto the window
+    pub fn buffer_coord_to_window(
+        &self,
+        buffer_coord: Vec2,
+        buffer_size: Vec2,
+        camera: Option<(&Camera, &GlobalTransform)>,
+    ) -> Result<Vec2> {
+        let RelativeQueryItem {
+            widget_size,
+            text_align,
+            sprite_global_transform,
+            ui_global_transform,
+            ui_node,
+            ..
+        } = self;
+        let transformation = WidgetBufferCoordTransformation::new(
+            text_align.vertical,
+            widget_size.physical_size()?,
+            widget_size.physical_content_rect()?,
+            buffer_size,
+        );
+        let widget_topleft =
+            transformation.buffer_to_widget(buffer_coord) / widget_size.scale_factor();
+        let render_target_size = widget_size.logical_size()?;
+
+        match self.scan()? {
+            SourceType::Sprite => {
+                let (camera, camera_transform) =
+                    camera.ok_or(RenderTargetError::SpriteExpectedCamera)?;
+                let relative_position = Vec3::new(
+                    widget_topleft.x - render_target_size.x / 2.,
+                    render_target_size.y / 2. - widget_topleft.y,
+                    0.,
+                );
+                let world_position = sprite_global_transform.transform_point(relative_position);
+                let viewport_position = camera
+                    .world_to_viewport(camera_transform, world_position)
+                    .map_err(|_| RenderTargetError::SpriteExpectedCamera)?;
+                let viewport_offset = camera
+                    .logical_viewport_rect()
+                    .map(|rect| rect.min)
+                    .unwrap_or_default();
+
+                Ok(viewport_position + viewport_offset)
+            }
+            SourceType::Ui => {
+                let ui_global_transform = ui_global_transform.ok_or_else(
+                    RenderTargetError::required_component_missing::<UiGlobalTransform>,
+                )?;
+                let ui_node =
+                    ui_node.ok_or_else(RenderTargetError::required_component_missing::<ComputedNode>)?;
+                let inverse_scale_factor = ui_node.inverse_scale_factor();
+
+                // UI transforms are in physical pixels, origined at the center of the node
+                let relative_position =
+                    (widget_topleft - render_target_size / 2.) / inverse_scale_factor;
+                let physical_position = ui_global_transform.transform_point2(relative_position);
+
+                Ok(physical_position * inverse_scale_factor)
+            }
+        }
+    }
 }
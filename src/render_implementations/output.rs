@@ -31,7 +31,9 @@ impl OutputToEntityItem<'_, '_> {
                     .sprite_target
                     .as_mut()
                     .ok_or_else(|| Into::<bevy::ecs::error::BevyError>::into(RenderTargetError::required_component_missing::<Sprite>()))?;
-                sprite.image = image.clone();
+                if sprite.image != *image {
+                    sprite.image = image.clone();
+                }
                 Ok(())
             }
             SourceType::Ui => {
@@ -39,7 +41,9 @@ impl OutputToEntityItem<'_, '_> {
                     .image_node_target
                     .as_mut()
                     .ok_or_else(|| Into::<bevy::ecs::error::BevyError>::into(RenderTargetError::required_component_missing::<ImageNode>()))?;
-                image_node.image = image.clone();
+                if image_node.image != *image {
+                    image_node.image = image.clone();
+                }
                 Ok(())
             }
         }
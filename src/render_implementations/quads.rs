@@ -0,0 +1,103 @@
+use bevy::ecs::query::QueryData;
+use bevy::sprite::Anchor;
+use render_implementations::prelude::*;
+
+use crate::prelude::*;
+use crate::render_implementations::Result;
+
+/// Places the quads of [`CosmicRenderMode::GlyphAtlas`](crate::CosmicRenderMode::GlyphAtlas)
+/// over a widget
+#[derive(QueryData)]
+pub(crate) struct QuadTarget {
+    scan: RenderTypeScan,
+
+    anchor: Option<&'static Anchor>,
+    ui: Option<&'static ComputedNode>,
+}
+
+impl<'w, 's> std::ops::Deref for QuadTargetItem<'w, 's> {
+    type Target = RenderTypeScanItem<'w, 's>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.scan
+    }
+}
+
+/// A plain or textured rectangle drawn over a widget
+pub(crate) struct Quad {
+    /// In logical pixels from the top left of the widget
+    pub rect: Rect,
+    pub color: Color,
+    /// The atlas entry shown, and the part of it within `rect`
+    pub texture: Option<(Handle<Image>, TextureAtlas, Rect)>,
+}
+
+impl QuadTargetItem<'_, '_> {
+    /// Inserts `quad` into `entity`, a child of the widget. `widget_size` is logical
+    pub fn insert_quad(
+        &self,
+        entity: &mut EntityCommands,
+        quad: Quad,
+        widget_size: Vec2,
+    ) -> Result<()> {
+        let textured = quad.texture.is_some();
+        // the default image is plain white
+        let (image, texture_atlas, rect) = match quad.texture {
+            Some((image, atlas, rect)) => (image, Some(atlas), Some(rect)),
+            None => (Handle::default(), None, None),
+        };
+        match self.scan()? {
+            SourceType::Sprite => {
+                // the widget is placed by its anchor, quads by their center, and y points up
+                let anchor = self.anchor.copied().unwrap_or_default().0;
+                let top_left = Vec2::new(
+                    -(0.5 + anchor.x) * widget_size.x,
+                    (0.5 - anchor.y) * widget_size.y,
+                );
+                let center = quad.rect.center();
+                entity.insert((
+                    Sprite {
+                        image,
+                        texture_atlas,
+                        rect,
+                        color: quad.color,
+                        custom_size: Some(quad.rect.size()),
+                        ..default()
+                    },
+                    Transform::from_xyz(
+                        top_left.x + center.x,
+                        top_left.y - center.y,
+                        // glyphs are drawn over the cursor and selection
+                        if textured { 0.002 } else { 0.001 },
+                    ),
+                ));
+            }
+            SourceType::Ui => {
+                // absolutely positioned children start within the border
+                let border = self.ui.map_or(Vec2::ZERO, |ui| {
+                    ui.border.min_inset * ui.inverse_scale_factor()
+                });
+                entity.insert((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(quad.rect.min.x - border.x),
+                        top: Val::Px(quad.rect.min.y - border.y),
+                        width: Val::Px(quad.rect.width()),
+                        height: Val::Px(quad.rect.height()),
+                        ..default()
+                    },
+                    ImageNode {
+                        image,
+                        texture_atlas,
+                        rect,
+                        color: quad.color,
+                        ..default()
+                    },
+                    // reused quads keep their place among the children
+                    ZIndex(textured as i32),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
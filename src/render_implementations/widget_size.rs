@@ -3,6 +3,7 @@ use render_implementations::{RenderTypeScan, RenderTypeScanItem};
 
 use crate::prelude::*;
 use crate::render_implementations::Result;
+use crate::CosmicPadding;
 use render_implementations::prelude::*;
 
 /// Query the (logical) size of a widget
@@ -12,6 +13,21 @@ pub struct CosmicWidgetSize {
 
     sprite: Option<&'static Sprite>,
     ui: Option<&'static ComputedNode>,
+    padding: Option<&'static CosmicPadding>,
+    scale: Option<&'static WidgetScaleFactor>,
+}
+
+/// Physical pixels per logical pixel a widget is rendered at.
+///
+/// Text in the [`CosmicEditBuffer`] is laid out in physical pixels, so buffer
+/// coordinates are physical too
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub(crate) struct WidgetScaleFactor(pub f32);
+
+impl Default for WidgetScaleFactor {
+    fn default() -> Self {
+        Self(1.)
+    }
 }
 
 /// Allows `.scan()` to be called on a [`CosmicWidgetSize`] through deref
@@ -40,6 +56,22 @@ impl NodeSizeExt for ComputedNode {
     }
 }
 
+/// Logical [`CosmicPadding`] at the top left and bottom right of a widget
+pub(crate) fn padding_insets(
+    padding: Option<&CosmicPadding>,
+    ui: Option<&ComputedNode>,
+) -> (Vec2, Vec2) {
+    let padding = padding.copied().unwrap_or_default();
+    let mut min = Vec2::new(padding.left, padding.top);
+    let mut max = Vec2::new(padding.right, padding.bottom);
+    if let (true, Some(ui)) = (padding.include_node, ui) {
+        let inset = ui.content_inset();
+        min += inset.min_inset * ui.inverse_scale_factor();
+        max += inset.max_inset * ui.inverse_scale_factor();
+    }
+    (min, max)
+}
+
 impl CosmicWidgetSizeItem<'_, '_> {
     /// Automatically logs any errors
     pub fn logical_size(&self) -> Result<Vec2> {
@@ -50,6 +82,35 @@ impl CosmicWidgetSizeItem<'_, '_> {
         ret
     }
 
+    /// The area the text is laid out in, after [`CosmicPadding`], in logical pixels
+    /// from the top left of the widget
+    pub fn content_rect(&self) -> Result<Rect> {
+        let size = self.logical_size()?;
+        let (min, max) = padding_insets(self.padding, self.ui);
+        let min = min.clamp(Vec2::ZERO, size);
+        Ok(Rect::from_corners(min, (size - max).clamp(min, size)))
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale.copied().unwrap_or_default().0
+    }
+
+    /// The size of the rendered image, see [`WidgetScaleFactor`]
+    pub fn physical_size(&self) -> Result<Vec2> {
+        Ok((self.logical_size()? * self.scale_factor()).round())
+    }
+
+    /// [`Self::content_rect`] in physical pixels
+    pub fn physical_content_rect(&self) -> Result<Rect> {
+        let content = self.content_rect()?;
+        let size = self.physical_size()?;
+        let min = (content.min * self.scale_factor()).min(size);
+        Ok(Rect::from_corners(
+            min,
+            (content.max * self.scale_factor()).clamp(min, size),
+        ))
+    }
+
     fn _logical_size(&self) -> Result<Vec2> {
         let source_type = self.scan.scan()?;
         match source_type {
@@ -0,0 +1,91 @@
+use bevy::{
+    camera::{visibility::RenderLayers, RenderTarget},
+    ecs::system::SystemParam,
+    window::PrimaryWindow,
+};
+
+use crate::prelude::*;
+
+/// Works out which camera a widget is shown by
+#[derive(SystemParam)]
+pub(crate) struct WidgetCameras<'w, 's> {
+    ui_cameras: Query<'w, 's, &'static ComputedUiTargetCamera>,
+    sprites: Query<'w, 's, Option<&'static RenderLayers>, With<Sprite>>,
+    cameras: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Camera,
+            &'static RenderTarget,
+            Option<&'static RenderLayers>,
+        ),
+    >,
+}
+
+impl WidgetCameras<'_, '_> {
+    /// The camera UI widgets are laid out for, or the first active camera
+    /// that sees the render layers of sprite widgets
+    pub fn camera_of(&self, widget: Entity) -> Option<Entity> {
+        if let Ok(ui_camera) = self.ui_cameras.get(widget) {
+            return ui_camera.get();
+        }
+
+        let layers = self.sprites.get(widget).ok()?.cloned().unwrap_or_default();
+        self.cameras
+            .iter()
+            .find(|(_, camera, _, camera_layers)| {
+                camera.is_active
+                    && camera_layers
+                        .cloned()
+                        .unwrap_or_default()
+                        .intersects(&layers)
+            })
+            .map(|(camera, ..)| camera)
+    }
+
+    fn target_of(&self, camera: Entity) -> Option<&RenderTarget> {
+        self.cameras
+            .get(camera)
+            .ok()
+            .map(|(_, _, target, _)| target)
+    }
+}
+
+/// Works out which window a widget is shown in
+#[derive(SystemParam)]
+pub(crate) struct WidgetWindows<'w, 's> {
+    cameras: WidgetCameras<'w, 's>,
+    primary_window: Query<'w, 's, Entity, With<PrimaryWindow>>,
+    windows: Query<'w, 's, (Entity, &'static Window)>,
+}
+
+impl WidgetWindows<'_, '_> {
+    pub fn window_of(&self, widget: Entity) -> Option<Entity> {
+        let camera = self.cameras.camera_of(widget)?;
+        match self.cameras.target_of(camera)? {
+            RenderTarget::Window(window) => window
+                .normalize(self.primary_window.single().ok())
+                .map(|window| window.entity()),
+            _ => None,
+        }
+    }
+
+    /// The window with keyboard focus, or the primary window
+    pub fn active_window(&self) -> Option<Entity> {
+        self.windows
+            .iter()
+            .find(|(_, window)| window.focused)
+            .map(|(entity, _)| entity)
+            .or(self.primary_window.single().ok())
+    }
+
+    /// Scale factor of the window a widget is shown in, or of the primary window
+    pub fn scale_factor_of(&self, widget: Entity) -> Option<f32> {
+        let window = self
+            .window_of(widget)
+            .or(self.primary_window.single().ok())?;
+        let (_, window) = self.windows.get(window).ok()?;
+        Some(window.scale_factor())
+    }
+}
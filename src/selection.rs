@@ -0,0 +1,228 @@
+//! Events for the cursor and selection of a widget.
+//!
+//! [`CursorMoved`] and [`SelectionChanged`] are triggered on a widget whenever its cursor
+//! or selection actually changes, whether by keyboard, mouse or an
+//! [`EditCommand`](crate::command::EditCommand). Gaining or losing focus only counts
+//! when the cursor ends up somewhere else, e.g. with [`FocusCursor::End`](crate::focus::FocusCursor).
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::selection::{CursorMoved, ReportSelectedText, SelectionChanged};
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands
+//!         .spawn((TextEdit, CosmicEditBuffer::default(), ReportSelectedText))
+//!         .observe(|moved: On<CursorMoved>| {
+//!             info!("Ln {}, Col {}", moved.new.line + 1, moved.new.index + 1);
+//!         })
+//!         .observe(|changed: On<SelectionChanged>| {
+//!             info!("Selected {:?}", changed.text);
+//!         });
+//! }
+//! ```
+
+use cosmic_text::{Cursor, Selection};
+
+use crate::{password::PasswordSet, prelude::*};
+
+/// System set for [`CursorMoved`] and [`SelectionChanged`]. Runs in [`PostUpdate`]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SelectionEventSet;
+
+pub(crate) fn plugin(app: &mut App) {
+    // cursors are in terms of the real text of a `Password` before `PasswordSet`
+    app.add_systems(
+        PostUpdate,
+        track_cursor.in_set(SelectionEventSet).before(PasswordSet),
+    )
+    .register_type::<ReportSelectedText>();
+}
+
+/// Triggered on a widget when its cursor moves
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct CursorMoved {
+    pub entity: Entity,
+    pub old: Cursor,
+    pub new: Cursor,
+}
+
+/// Triggered on a widget when its selection changes
+#[derive(EntityEvent, Debug, Clone)]
+pub struct SelectionChanged {
+    pub entity: Entity,
+    pub old: Selection,
+    pub new: Selection,
+    /// The selected text, only for widgets with [`ReportSelectedText`]
+    pub text: Option<String>,
+}
+
+/// Include the selected text in [`SelectionChanged`]
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct ReportSelectedText;
+
+/// The cursor and selection last reported for a widget
+#[derive(Component, Debug, Clone, Copy)]
+pub(crate) struct TrackedCursor {
+    cursor: Cursor,
+    selection: Selection,
+}
+
+impl Default for TrackedCursor {
+    fn default() -> Self {
+        Self {
+            cursor: Cursor::new(0, 0),
+            selection: Selection::None,
+        }
+    }
+}
+
+fn track_cursor(
+    mut widgets: Query<(
+        Entity,
+        EditorBuffer,
+        &mut TrackedCursor,
+        Has<ReportSelectedText>,
+    )>,
+    mut commands: Commands,
+) {
+    for (entity, mut buffer, mut tracked, report_text) in widgets.iter_mut() {
+        // falls back to the `SavedCursor` while unfocused, which the editor starts from
+        let cursor = buffer.cursor();
+        let selection = buffer.selection();
+        if tracked.cursor == cursor && tracked.selection == selection {
+            continue;
+        }
+        let old = std::mem::replace(&mut *tracked, TrackedCursor { cursor, selection });
+
+        if old.cursor != cursor {
+            commands.trigger(CursorMoved {
+                entity,
+                old: old.cursor,
+                new: cursor,
+            });
+        }
+        if old.selection != selection {
+            commands.trigger(SelectionChanged {
+                entity,
+                old: old.selection,
+                new: selection,
+                text: report_text.then(|| buffer.selected_text()).flatten(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{Attrs, Metrics};
+
+    use super::*;
+    #[allow(deprecated)]
+    use crate::input::CosmicTextChanged;
+    use crate::{change::CosmicTextEdited, command::EditCommand};
+
+    #[derive(Resource, Default)]
+    struct Seen(Vec<String>);
+
+    #[test]
+    #[allow(deprecated)]
+    fn only_real_changes_are_reported() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<Seen>()
+            .add_message::<CosmicTextChanged>()
+            .add_message::<CosmicTextEdited>()
+            .add_plugins((crate::command::plugin, plugin));
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.)).with_text(
+            &mut font_system,
+            "hello",
+            Attrs::new(),
+        );
+        app.insert_resource(CosmicFontSystem(font_system));
+        let entity = app
+            .world_mut()
+            .spawn((buffer, ReportSelectedText))
+            .observe(|moved: On<CursorMoved>, mut seen: ResMut<Seen>| {
+                seen.0.push(format!("moved to {}", moved.new.index));
+            })
+            .observe(|changed: On<SelectionChanged>, mut seen: ResMut<Seen>| {
+                seen.0.push(format!("selected {:?}", changed.text));
+            })
+            .id();
+
+        for command in [
+            EditCommand::SelectAll,
+            EditCommand::SelectAll,
+            EditCommand::SetCursor(Cursor::new(0, 2)),
+        ] {
+            app.world_mut()
+                .trigger(CosmicEditCommand::new(entity, command));
+            app.update();
+        }
+
+        assert_eq!(
+            app.world().resource::<Seen>().0,
+            [
+                "moved to 5",
+                "selected Some(\"hello\")",
+                "moved to 2",
+                "selected None"
+            ]
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn moves_while_gaining_focus_are_reported() {
+        use crate::focus::{FocusCursor, RequestFocus};
+
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<Seen>()
+            .add_message::<CosmicTextChanged>()
+            .add_message::<CosmicTextEdited>()
+            .add_plugins((crate::command::plugin, crate::focus::plugin, plugin));
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.)).with_text(
+            &mut font_system,
+            "hello",
+            Attrs::new(),
+        );
+        app.insert_resource(CosmicFontSystem(font_system));
+        let entity = app
+            .world_mut()
+            .spawn(buffer)
+            .observe(|moved: On<CursorMoved>, mut seen: ResMut<Seen>| {
+                seen.0.push(format!("moved to {}", moved.new.index));
+            })
+            .id();
+        app.update();
+
+        // the command moves the saved cursor, then the editor starts at the end
+        app.world_mut()
+            .trigger(RequestFocus::new(entity).with_cursor(FocusCursor::End));
+        app.world_mut().trigger(CosmicEditCommand::new(
+            entity,
+            EditCommand::SetCursor(Cursor::new(0, 2)),
+        ));
+        app.update();
+        // the editor is added once the focus systems ran
+        app.update();
+        app.world_mut().trigger(CosmicEditCommand::new(
+            entity,
+            EditCommand::SetCursor(Cursor::new(0, 1)),
+        ));
+        app.update();
+        assert!(app.world().get::<CosmicEditor>(entity).is_some());
+
+        assert_eq!(
+            app.world().resource::<Seen>().0,
+            ["moved to 2", "moved to 5", "moved to 1"]
+        );
+    }
+}
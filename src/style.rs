@@ -0,0 +1,433 @@
+//! Recoloring widgets as they are hovered, focused, made [`ReadOnly`] etc.
+//!
+//! A [`CosmicWidgetStyle`] holds a [`StateStyle`] for each state. Every frame the states
+//! a widget is in are layered on top of [`CosmicWidgetStyle::normal`], in the order of
+//! the fields, and the result is written to [`CosmicBackgroundColor`], [`CursorColor`]
+//! and the [`BorderColor`] of UI widgets. Colors no state sets are left alone.
+//!
+//! The colors a widget has without any states are taken from those components, and
+//! from [`DefaultAttrs`] for text. Changing them later changes the base colors too.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::style::{CosmicWidgetStyle, StateStyle};
+//!
+//! fn setup(mut commands: Commands) {
+//!     commands.spawn((
+//!         TextEdit,
+//!         CosmicEditBuffer::default(),
+//!         CosmicWidgetStyle {
+//!             hovered: StateStyle::default().background(Color::srgb(0.9, 0.9, 0.9)),
+//!             focused: StateStyle::default().border(Color::srgb(0.2, 0.4, 1.0)),
+//!             disabled: StateStyle::default().text(Color::srgb(0.5, 0.5, 0.5)),
+//!             transition: 0.15,
+//!             ..default()
+//!         },
+//!     ));
+//! }
+//! ```
+
+use crate::{
+    filter::InputValidity, input::InputState, password::PasswordSet, prelude::*, render::RenderSet,
+    CosmicBackgroundColor, CursorColor,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        (init_style, refresh_style_base, apply_style)
+            .chain()
+            .after(PasswordSet)
+            .before(RenderSet),
+    )
+    .register_type::<StateStyle>()
+    .register_type::<CosmicWidgetStyle>();
+}
+
+/// Colors for one state of a widget, `None` keeps the color of the states below
+#[derive(Reflect, Default, Debug, Clone, Copy, PartialEq)]
+pub struct StateStyle {
+    /// Replaces the [`CosmicBackgroundColor`]
+    pub background: Option<Color>,
+    /// Replaces the color of all text, including colors set with [`Attrs`](cosmic_text::Attrs)
+    pub text: Option<Color>,
+    /// Replaces the [`BorderColor`] of UI widgets
+    pub border: Option<Color>,
+    /// Replaces the [`CursorColor`]
+    pub cursor: Option<Color>,
+}
+
+impl StateStyle {
+    pub fn background(mut self, color: impl Into<Color>) -> Self {
+        self.background = Some(color.into());
+        self
+    }
+
+    pub fn text(mut self, color: impl Into<Color>) -> Self {
+        self.text = Some(color.into());
+        self
+    }
+
+    pub fn border(mut self, color: impl Into<Color>) -> Self {
+        self.border = Some(color.into());
+        self
+    }
+
+    pub fn cursor(mut self, color: impl Into<Color>) -> Self {
+        self.cursor = Some(color.into());
+        self
+    }
+
+    fn apply_to(&self, colors: &mut StyleColors) {
+        colors.background = self.background.unwrap_or(colors.background);
+        colors.text = self.text.unwrap_or(colors.text);
+        colors.border = self.border.unwrap_or(colors.border);
+        colors.cursor = self.cursor.unwrap_or(colors.cursor);
+    }
+}
+
+/// Per-state colors of a widget, see the [module docs](self)
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component)]
+pub struct CosmicWidgetStyle {
+    pub normal: StateStyle,
+    /// While the pointer is over the widget or dragging in it
+    pub hovered: StateStyle,
+    pub focused: StateStyle,
+    pub readonly: StateStyle,
+    /// While [`InputValidity::Invalid`]
+    pub invalid: StateStyle,
+    pub disabled: StateStyle,
+    /// Seconds taken to fade between colors, `0.` changes them at once
+    pub transition: f32,
+}
+
+impl CosmicWidgetStyle {
+    fn states(&self) -> [&StateStyle; 6] {
+        [
+            &self.normal,
+            &self.hovered,
+            &self.focused,
+            &self.readonly,
+            &self.invalid,
+            &self.disabled,
+        ]
+    }
+
+    fn styles_text(&self) -> bool {
+        self.states().iter().any(|state| state.text.is_some())
+    }
+}
+
+/// Text color set by a [`CosmicWidgetStyle`], used when rendering
+#[derive(Component, Debug, Clone, Copy)]
+pub(crate) struct StyledTextColor(pub Color);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StyleColors {
+    background: Color,
+    text: Color,
+    border: Color,
+    cursor: Color,
+}
+
+impl StyleColors {
+    fn mix(&self, other: &Self, t: f32) -> Self {
+        if t >= 1. {
+            return *other;
+        }
+        let mix = |a: Color, b: Color| -> Color {
+            LinearRgba::from(a).mix(&LinearRgba::from(b), t).into()
+        };
+        Self {
+            background: mix(self.background, other.background),
+            text: mix(self.text, other.text),
+            border: mix(self.border, other.border),
+            cursor: mix(self.cursor, other.cursor),
+        }
+    }
+}
+
+/// Colors of a styled widget without any states, and the transition it is in
+#[derive(Component, Debug)]
+pub(crate) struct StyleState {
+    base: StyleColors,
+    from: StyleColors,
+    to: StyleColors,
+    elapsed: f32,
+    /// The colors of the components after [`apply_style`] last ran, to tell its own
+    /// changes apart from changes made elsewhere
+    applied: StyleColors,
+}
+
+impl StyleState {
+    /// Replaces the given base colors, e.g. with the colors of a new theme, while the
+    /// components still hold the colors of the current state
+    pub(crate) fn set_base(
+        &mut self,
+        background: Option<Color>,
+        text: Option<Color>,
+        cursor: Option<Color>,
+    ) {
+        self.base.background = background.unwrap_or(self.base.background);
+        self.base.text = text.unwrap_or(self.base.text);
+        self.base.cursor = cursor.unwrap_or(self.base.cursor);
+    }
+}
+
+fn text_color(attrs: &DefaultAttrs) -> Color {
+    let [r, g, b, a] = attrs.0.color_opt.map_or([0, 0, 0, 255], |c| c.as_rgba());
+    Color::srgba_u8(r, g, b, a)
+}
+
+fn init_style(
+    mut commands: Commands,
+    widgets: Query<
+        (
+            Entity,
+            &CosmicBackgroundColor,
+            &DefaultAttrs,
+            &CursorColor,
+            Option<&BorderColor>,
+        ),
+        (With<CosmicWidgetStyle>, Without<StyleState>),
+    >,
+) {
+    for (entity, background, attrs, cursor, border_opt) in widgets.iter() {
+        let base = StyleColors {
+            background: background.0,
+            text: text_color(attrs),
+            border: border_opt.map_or(Color::NONE, |border| border.top),
+            cursor: cursor.0,
+        };
+        commands.entity(entity).insert(StyleState {
+            base,
+            from: base,
+            to: base,
+            elapsed: 0.,
+            applied: base,
+        });
+    }
+}
+
+/// Takes colors set outside of [`apply_style`] as the new base colors
+fn refresh_style_base(
+    mut widgets: Query<(
+        &mut StyleState,
+        Ref<CosmicBackgroundColor>,
+        Ref<DefaultAttrs>,
+        Ref<CursorColor>,
+        Option<Ref<BorderColor>>,
+    )>,
+) {
+    for (mut state, background, attrs, cursor, border_opt) in widgets.iter_mut() {
+        if background.is_changed() && background.0 != state.applied.background {
+            state.base.background = background.0;
+        }
+        if attrs.is_changed() {
+            state.base.text = text_color(&attrs);
+        }
+        if cursor.is_changed() && cursor.0 != state.applied.cursor {
+            state.base.cursor = cursor.0;
+        }
+        if let Some(border) = border_opt {
+            if border.is_changed() && border.top != state.applied.border {
+                state.base.border = border.top;
+            }
+        }
+    }
+}
+
+fn apply_style(
+    mut commands: Commands,
+    mut widgets: Query<(
+        Entity,
+        &CosmicWidgetStyle,
+        &mut StyleState,
+        &mut CosmicBackgroundColor,
+        &mut CursorColor,
+        Option<&mut BorderColor>,
+        Option<&mut StyledTextColor>,
+        (
+            Option<&InputState>,
+            Has<ReadOnly>,
+            Has<Disabled>,
+            Option<&InputValidity>,
+        ),
+    )>,
+    focused_widget: Res<FocusedWidget>,
+    time: Res<Time>,
+) {
+    for (
+        entity,
+        style,
+        mut state,
+        mut background,
+        mut cursor,
+        border_opt,
+        text_opt,
+        (input_state, readonly, disabled, validity),
+    ) in widgets.iter_mut()
+    {
+        let hovered = matches!(
+            input_state,
+            Some(InputState::Hovering | InputState::Dragging { .. })
+        );
+        let active = [
+            true,
+            hovered,
+            focused_widget.0 == Some(entity),
+            readonly,
+            validity == Some(&InputValidity::Invalid),
+            disabled,
+        ];
+        let mut target = state.base;
+        for (state_style, _) in style.states().into_iter().zip(active).filter(|(_, a)| *a) {
+            state_style.apply_to(&mut target);
+        }
+
+        let progress = |state: &StyleState| {
+            if style.transition > 0. {
+                (state.elapsed / style.transition).min(1.)
+            } else {
+                1.
+            }
+        };
+        if target != state.to {
+            // start from wherever the last transition got to
+            let current = state.from.mix(&state.to, progress(&state));
+            state.from = current;
+            state.to = target;
+            state.elapsed = 0.;
+        }
+        state.elapsed += time.delta_secs();
+        let current = state.from.mix(&state.to, progress(&state));
+
+        let states = style.states();
+        if states.iter().any(|s| s.background.is_some()) && background.0 != current.background {
+            background.0 = current.background;
+        }
+        if states.iter().any(|s| s.cursor.is_some()) && cursor.0 != current.cursor {
+            cursor.0 = current.cursor;
+        }
+        if let Some(mut border) = border_opt {
+            if states.iter().any(|s| s.border.is_some()) && border.top != current.border {
+                *border = BorderColor::all(current.border);
+            }
+            state.applied.border = border.top;
+        }
+        state.applied.background = background.0;
+        state.applied.cursor = cursor.0;
+        match (style.styles_text(), text_opt) {
+            (true, Some(mut text)) => {
+                if text.0 != current.text {
+                    text.0 = current.text;
+                }
+            }
+            (true, None) => {
+                commands
+                    .entity(entity)
+                    .insert(StyledTextColor(current.text));
+            }
+            (false, Some(_)) => {
+                commands.entity(entity).remove::<StyledTextColor>();
+            }
+            (false, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::Metrics;
+
+    use super::*;
+
+    #[test]
+    fn later_states_win() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<Time>()
+            .add_plugins(crate::focus::plugin)
+            .add_systems(
+                PostUpdate,
+                (init_style, refresh_style_base, apply_style).chain(),
+            );
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let style = CosmicWidgetStyle {
+            focused: StateStyle::default().background(Color::WHITE),
+            disabled: StateStyle::default().background(Color::BLACK),
+            ..default()
+        };
+        let widget = app
+            .world_mut()
+            .spawn((buffer, CosmicBackgroundColor(Color::NONE), style))
+            .id();
+        let background = |app: &App| app.world().get::<CosmicBackgroundColor>(widget).unwrap().0;
+
+        app.update();
+        assert_eq!(background(&app), Color::NONE);
+
+        app.world_mut()
+            .resource_mut::<FocusedWidget>()
+            .focus(widget);
+        app.update();
+        assert_eq!(background(&app), Color::WHITE);
+
+        // disabling also unfocuses
+        app.world_mut().entity_mut(widget).insert(Disabled);
+        app.update();
+        assert_eq!(background(&app), Color::BLACK);
+
+        app.world_mut().entity_mut(widget).remove::<Disabled>();
+        app.update();
+        assert_eq!(background(&app), Color::NONE);
+    }
+
+    #[test]
+    fn outside_changes_update_the_base() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<Time>()
+            .add_plugins(crate::focus::plugin)
+            .add_systems(
+                PostUpdate,
+                (init_style, refresh_style_base, apply_style).chain(),
+            );
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let style = CosmicWidgetStyle {
+            focused: StateStyle::default().background(Color::WHITE),
+            ..default()
+        };
+        let widget = app
+            .world_mut()
+            .spawn((buffer, CosmicBackgroundColor(Color::NONE), style))
+            .id();
+        let background = |app: &App| app.world().get::<CosmicBackgroundColor>(widget).unwrap().0;
+        let red = Color::srgb(1., 0., 0.);
+        app.update();
+
+        app.world_mut()
+            .entity_mut(widget)
+            .insert(CosmicBackgroundColor(red));
+        app.update();
+        assert_eq!(background(&app), red);
+
+        app.world_mut()
+            .resource_mut::<FocusedWidget>()
+            .focus(widget);
+        app.update();
+        assert_eq!(background(&app), Color::WHITE);
+
+        app.world_mut().resource_mut::<FocusedWidget>().unfocus();
+        app.update();
+        assert_eq!(background(&app), red);
+    }
+}
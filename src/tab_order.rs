@@ -0,0 +1,205 @@
+//! Moving focus between widgets with Tab and Shift+Tab.
+//!
+//! Only widgets with a [`CosmicTabIndex`] take part. Widgets with a positive index come
+//! first, lowest first, followed by widgets with index `0` in the order they were given
+//! their [`CosmicTabIndex`], like the HTML `tabindex` attribute. [`ReadOnly`], [`Disabled`] and hidden
+//! widgets are skipped.
+//!
+//! Each window has its own tab order, which works the same for [`TextEdit`] and
+//! [`TextEdit2d`] widgets. Sprites belong to the window of the first active camera
+//! that can see their [`RenderLayers`](bevy::camera::visibility::RenderLayers).
+//!
+//! The keys can be changed with [`KeyCommand::FocusNext`] and
+//! [`KeyCommand::FocusPrevious`] in the [`CosmicKeymap`].
+//!
+//! With the `input_focus` feature, Tab only moves on from a focused widget here. Give
+//! widgets bevy's `TabIndex` as well to tab onto them from other UI.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_cosmic_edit::prelude::*;
+//! use bevy_cosmic_edit::tab_order::CosmicTabIndex;
+//!
+//! fn setup(mut commands: Commands) {
+//!     for _ in 0..3 {
+//!         commands.spawn((TextEdit, CosmicEditBuffer::default(), CosmicTabIndex(0)));
+//!     }
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    input::{
+        ime::ImePreedit,
+        keymap::{CosmicKeymap, KeyCommand},
+        InputSet,
+    },
+    prelude::*,
+    render_implementations::WidgetWindows,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(Update, tab_navigation.after(InputSet))
+        .register_type::<CosmicTabIndex>();
+}
+
+/// Position of a widget in the tab order, see the [module docs](self).
+///
+/// Negative values leave the widget out of the tab order
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+#[require(TabSequence = TabSequence::next())]
+pub struct CosmicTabIndex(pub i32);
+
+impl CosmicTabIndex {
+    /// Positive indices first, then `0`s in the order they were added
+    fn sort_key(self, sequence: TabSequence, entity: Entity) -> (bool, i32, u64, Entity) {
+        (self.0 == 0, self.0, sequence.0, entity)
+    }
+}
+
+/// When a widget got its [`CosmicTabIndex`], since entities are reused and don't
+/// sort in spawn order
+#[derive(Component, Debug, Clone, Copy)]
+struct TabSequence(u64);
+
+impl TabSequence {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+fn tab_navigation(
+    keys: Res<ButtonInput<KeyCode>>,
+    keymap: Res<CosmicKeymap>,
+    mut focused_widget: ResMut<FocusedWidget>,
+    focused_q: Query<(Option<&CosmicKeymap>, Option<&ImePreedit>)>,
+    widgets: Query<(
+        Entity,
+        &CosmicTabIndex,
+        &TabSequence,
+        Has<ReadOnly>,
+        Has<Disabled>,
+        Option<&InheritedVisibility>,
+    )>,
+    windows: WidgetWindows,
+) {
+    let focused = focused_widget.0;
+    let (keymap_opt, preedit_opt) = focused
+        .and_then(|e| focused_q.get(e).ok())
+        .unwrap_or_default();
+    // the IME uses Tab while composing
+    if preedit_opt.is_some_and(ImePreedit::is_composing) {
+        return;
+    }
+    let forward = match keymap_opt.unwrap_or(&keymap).just_pressed(&keys) {
+        Some((KeyCommand::FocusNext, _)) => true,
+        Some((KeyCommand::FocusPrevious, _)) => false,
+        _ => return,
+    };
+
+    // bevy's tab navigation moves focus onto widgets instead
+    #[cfg(feature = "input_focus")]
+    if focused.is_none() {
+        return;
+    }
+
+    let current = match focused {
+        Some(focused) => match widgets.get(focused) {
+            Ok((_, tab_index, sequence, ..)) => Some(tab_index.sort_key(*sequence, focused)),
+            // widgets outside the tab order keep Tab to themselves
+            Err(_) => return,
+        },
+        None => None,
+    };
+    let window = match focused {
+        Some(focused) => windows.window_of(focused),
+        None => windows.active_window(),
+    };
+
+    let mut order: Vec<_> = widgets
+        .iter()
+        .filter(|(entity, tab_index, _, readonly, disabled, visibility)| {
+            tab_index.0 >= 0
+                && !readonly
+                && !disabled
+                && visibility.is_none_or(|visibility| visibility.get())
+                && Some(*entity) != focused
+                && windows.window_of(*entity) == window
+        })
+        .map(|(entity, tab_index, sequence, ..)| tab_index.sort_key(*sequence, entity))
+        .collect();
+    order.sort_unstable();
+
+    let next = match (current, forward) {
+        (Some(current), true) => order.iter().find(|key| **key > current).or(order.first()),
+        (Some(current), false) => order
+            .iter()
+            .rev()
+            .find(|key| **key < current)
+            .or(order.last()),
+        (None, true) => order.first(),
+        (None, false) => order.last(),
+    };
+    if let Some((.., next)) = next {
+        focused_widget.focus(*next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::Metrics;
+
+    use super::*;
+
+    fn spawn(app: &mut App, tab_index: i32) -> Entity {
+        app.world_mut()
+            .resource_scope(|world, mut font_system: Mut<CosmicFontSystem>| {
+                let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+                world.spawn((buffer, CosmicTabIndex(tab_index))).id()
+            })
+    }
+
+    fn tab(app: &mut App, shift: bool) -> Entity {
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.reset_all();
+        if shift {
+            keys.press(KeyCode::ShiftLeft);
+        }
+        keys.press(KeyCode::Tab);
+        app.update();
+        app.world().resource::<FocusedWidget>().0.unwrap()
+    }
+
+    #[test]
+    fn skips_read_only_and_wraps() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<CosmicKeymap>()
+            .insert_resource(CosmicFontSystem(
+                cosmic_text::FontSystem::new_with_fonts([]),
+            ))
+            .add_systems(Update, tab_navigation);
+
+        let [zero, two, read_only, one, _hidden] = [0, 2, 3, 1, -1].map(|i| spawn(&mut app, i));
+        app.world_mut().entity_mut(read_only).insert(ReadOnly);
+        app.insert_resource(FocusedWidget(Some(zero)));
+
+        let forward = [(); 4].map(|_| tab(&mut app, false));
+        assert_eq!(forward, [one, two, zero, one]);
+        let backward = [(); 4].map(|_| tab(&mut app, true));
+        assert_eq!(backward, [zero, two, one, zero]);
+
+        // `0`s keep the order they were spawned in, even when entities are reused
+        app.world_mut().despawn(one);
+        let late = spawn(&mut app, 0);
+        let later = spawn(&mut app, 0);
+        app.world_mut().despawn(two);
+        let latest = spawn(&mut app, 0);
+        let forward = [(); 4].map(|_| tab(&mut app, false));
+        assert_eq!(forward, [late, later, latest, zero]);
+    }
+}
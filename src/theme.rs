@@ -0,0 +1,366 @@
+//! Default colors for all widgets, see [`CosmicTheme`].
+//!
+//! When a widget is spawned without any of [`CosmicBackgroundColor`], [`CursorColor`],
+//! [`SelectionColor`] and [`HoverCursor`], or with [`DefaultAttrs`] that have no color,
+//! those are taken from the current theme. Changing the [`CosmicTheme`], e.g. switching
+//! to [`ThemeVariant::Dark`], updates them on existing widgets. Colors that were spawned
+//! with the widget or inserted later stay as they are.
+//!
+//! ```
+//! # use bevy::prelude::*;
+//! use bevy_cosmic_edit::theme::{CosmicTheme, ThemeVariant};
+//!
+//! fn toggle_theme(keys: Res<ButtonInput<KeyCode>>, mut theme: ResMut<CosmicTheme>) {
+//!     if keys.just_pressed(KeyCode::F2) {
+//!         theme.variant = match theme.variant {
+//!             ThemeVariant::Light => ThemeVariant::Dark,
+//!             ThemeVariant::Dark => ThemeVariant::Light,
+//!         };
+//!     }
+//! }
+//! ```
+
+use bevy::ecs::world::DeferredWorld;
+use bevy::window::{CursorIcon, SystemCursorIcon};
+
+use crate::{
+    password::PasswordSet, prelude::*, style::StyleState, CosmicBackgroundColor, CursorColor,
+    HoverCursor, SelectionColor,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        apply_theme
+            .run_if(resource_changed::<CosmicTheme>)
+            .before(PasswordSet),
+    )
+    .add_observer(theme_new_widget)
+    .add_observer(mark_explicit::<CosmicBackgroundColor>(|themed| {
+        &mut themed.background
+    }))
+    .add_observer(mark_explicit::<CursorColor>(|themed| &mut themed.cursor))
+    .add_observer(mark_explicit::<SelectionColor>(|themed| {
+        &mut themed.selection
+    }))
+    .add_observer(mark_explicit::<HoverCursor>(|themed| {
+        &mut themed.hover_cursor
+    }))
+    .add_observer(mark_explicit_text)
+    .init_resource::<CosmicTheme>()
+    .register_type::<CosmicTheme>();
+}
+
+/// Which of the [`CosmicTheme`]'s variants is used
+#[derive(Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeVariant {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// Defaults for one [`ThemeVariant`]
+#[derive(Reflect, Debug, Clone, PartialEq)]
+pub struct ThemeDefaults {
+    pub background: Color,
+    pub text: Color,
+    pub cursor: Color,
+    pub selection: Color,
+    pub hover_cursor: CursorIcon,
+}
+
+impl ThemeDefaults {
+    /// Same as the components' own defaults
+    pub fn light() -> Self {
+        Self {
+            background: Color::WHITE,
+            text: Color::BLACK,
+            cursor: Color::BLACK,
+            selection: bevy::color::palettes::basic::GRAY.into(),
+            hover_cursor: CursorIcon::System(SystemCursorIcon::Text),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            background: Color::srgb(0.12, 0.12, 0.14),
+            text: Color::srgb(0.9, 0.9, 0.9),
+            cursor: Color::srgb(0.9, 0.9, 0.9),
+            selection: Color::srgb(0.25, 0.35, 0.55),
+            hover_cursor: CursorIcon::System(SystemCursorIcon::Text),
+        }
+    }
+}
+
+/// Default widget colors, see the [module docs](self)
+#[derive(Resource, Reflect, Debug, Clone, PartialEq)]
+#[reflect(Resource)]
+pub struct CosmicTheme {
+    pub variant: ThemeVariant,
+    pub light: ThemeDefaults,
+    pub dark: ThemeDefaults,
+}
+
+impl Default for CosmicTheme {
+    fn default() -> Self {
+        Self {
+            variant: ThemeVariant::Light,
+            light: ThemeDefaults::light(),
+            dark: ThemeDefaults::dark(),
+        }
+    }
+}
+
+impl CosmicTheme {
+    /// The defaults of the current [`ThemeVariant`]
+    pub fn current(&self) -> &ThemeDefaults {
+        match self.variant {
+            ThemeVariant::Light => &self.light,
+            ThemeVariant::Dark => &self.dark,
+        }
+    }
+}
+
+/// Which components of a widget follow the [`CosmicTheme`]
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub(crate) struct Themed {
+    background: bool,
+    text: bool,
+    cursor: bool,
+    selection: bool,
+    hover_cursor: bool,
+}
+
+type ThemedComponents = (
+    &'static mut CosmicBackgroundColor,
+    &'static mut DefaultAttrs,
+    &'static mut CursorColor,
+    &'static mut SelectionColor,
+    &'static mut HoverCursor,
+);
+
+/// Adds the themed components a new widget was spawned without, along with [`Themed`].
+///
+/// Called when a [`CosmicEditBuffer`] is added, before its observers run
+pub(crate) fn insert_themed_components(world: &mut DeferredWorld, entity: Entity) {
+    let widget = world.entity(entity);
+    let themed = Themed {
+        background: !widget.contains::<CosmicBackgroundColor>(),
+        text: widget
+            .get::<DefaultAttrs>()
+            .is_none_or(|attrs| attrs.0.color_opt.is_none()),
+        cursor: !widget.contains::<CursorColor>(),
+        selection: !widget.contains::<SelectionColor>(),
+        hover_cursor: !widget.contains::<HoverCursor>(),
+    };
+    let mut commands = world.commands();
+    let mut widget = commands.entity(entity);
+    if themed.background {
+        widget.insert_if_new(CosmicBackgroundColor::default());
+    }
+    if themed.cursor {
+        widget.insert_if_new(CursorColor::default());
+    }
+    if themed.selection {
+        widget.insert_if_new(SelectionColor::default());
+    }
+    if themed.hover_cursor {
+        widget.insert_if_new(HoverCursor::default());
+    }
+    // separately, so inserting the defaults doesn't count as explicit
+    widget.insert(themed);
+}
+
+fn theme_new_widget(
+    add: On<Add, Themed>,
+    theme: Option<Res<CosmicTheme>>,
+    mut widgets: Query<(&Themed, ThemedComponents)>,
+) {
+    let Some(theme) = theme else {
+        return;
+    };
+    if let Ok((themed, components)) = widgets.get_mut(add.entity) {
+        apply_defaults(*themed, theme.current(), components);
+    }
+}
+
+/// Stops a component from following the theme once it is inserted
+fn mark_explicit<C: Component>(
+    field: fn(&mut Themed) -> &mut bool,
+) -> impl FnMut(On<Insert, C>, Query<&mut Themed>) {
+    move |insert, mut widgets| {
+        if let Ok(mut themed) = widgets.get_mut(insert.entity) {
+            *field(&mut themed) = false;
+        }
+    }
+}
+
+fn mark_explicit_text(
+    insert: On<Insert, DefaultAttrs>,
+    mut widgets: Query<(&mut Themed, &DefaultAttrs)>,
+) {
+    if let Ok((mut themed, attrs)) = widgets.get_mut(insert.entity) {
+        if attrs.0.color_opt.is_some() {
+            themed.text = false;
+        }
+    }
+}
+
+fn apply_theme(
+    theme: Res<CosmicTheme>,
+    mut widgets: Query<(&Themed, ThemedComponents, Option<&mut StyleState>)>,
+) {
+    let defaults = theme.current();
+    for (themed, components, style_state) in widgets.iter_mut() {
+        apply_defaults(*themed, defaults, components);
+        // the components may hold the colors of a CosmicWidgetStyle state,
+        // so its base colors are set directly
+        if let Some(mut style_state) = style_state {
+            style_state.set_base(
+                themed.background.then_some(defaults.background),
+                themed.text.then_some(defaults.text),
+                themed.cursor.then_some(defaults.cursor),
+            );
+        }
+    }
+}
+
+fn apply_defaults(
+    themed: Themed,
+    defaults: &ThemeDefaults,
+    (mut background, mut attrs, mut cursor, mut selection, mut hover_cursor): (
+        Mut<CosmicBackgroundColor>,
+        Mut<DefaultAttrs>,
+        Mut<CursorColor>,
+        Mut<SelectionColor>,
+        Mut<HoverCursor>,
+    ),
+) {
+    if themed.background && background.0 != defaults.background {
+        background.0 = defaults.background;
+    }
+    let text = Some(defaults.text.to_cosmic());
+    if themed.text && attrs.0.color_opt != text {
+        attrs.0.color_opt = text;
+    }
+    if themed.cursor && cursor.0 != defaults.cursor {
+        cursor.0 = defaults.cursor;
+    }
+    if themed.selection && selection.0 != defaults.selection {
+        selection.0 = defaults.selection;
+    }
+    if themed.hover_cursor && hover_cursor.0 != defaults.hover_cursor {
+        hover_cursor.0 = defaults.hover_cursor.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::Metrics;
+
+    use super::*;
+    use crate::style::{CosmicWidgetStyle, StateStyle};
+
+    #[test]
+    fn switching_variant_keeps_explicit_colors() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>().add_plugins(plugin);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let red = Color::srgb(1., 0., 0.);
+        let widget = app.world_mut().spawn((buffer, CursorColor(red))).id();
+        app.update();
+
+        app.world_mut().resource_mut::<CosmicTheme>().variant = ThemeVariant::Dark;
+        app.update();
+        let dark = ThemeDefaults::dark();
+        let widget = app.world().entity(widget);
+        assert_eq!(
+            widget.get::<CosmicBackgroundColor>().unwrap().0,
+            dark.background
+        );
+        assert_eq!(widget.get::<SelectionColor>().unwrap().0, dark.selection);
+        assert_eq!(widget.get::<CursorColor>().unwrap().0, red);
+        assert_eq!(
+            widget.get::<DefaultAttrs>().unwrap().0.color_opt,
+            Some(dark.text.to_cosmic())
+        );
+    }
+
+    #[test]
+    fn explicit_colors_are_kept_even_if_they_match_the_default() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>().add_plugins(plugin);
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let widget = app
+            .world_mut()
+            .spawn((buffer, CosmicBackgroundColor(Color::WHITE)))
+            .id();
+        app.update();
+        app.world_mut()
+            .entity_mut(widget)
+            .insert(SelectionColor::default());
+
+        app.world_mut().resource_mut::<CosmicTheme>().variant = ThemeVariant::Dark;
+        app.update();
+        let dark = ThemeDefaults::dark();
+        let widget = app.world().entity(widget);
+        assert_eq!(
+            widget.get::<CosmicBackgroundColor>().unwrap().0,
+            Color::WHITE
+        );
+        assert_eq!(
+            widget.get::<SelectionColor>().unwrap().0,
+            SelectionColor::default().0
+        );
+        assert_eq!(widget.get::<CursorColor>().unwrap().0, dark.cursor);
+    }
+
+    #[test]
+    fn switching_variant_while_styled_and_focused() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<Time>()
+            .add_plugins((plugin, crate::style::plugin, crate::focus::plugin));
+
+        let mut font_system = cosmic_text::FontSystem::new_with_fonts([]);
+        let buffer = CosmicEditBuffer::new(&mut font_system, Metrics::new(20., 20.));
+        app.insert_resource(CosmicFontSystem(font_system));
+        let red = Color::srgb(1., 0., 0.);
+        let style = CosmicWidgetStyle {
+            focused: StateStyle::default()
+                .background(Color::WHITE)
+                .cursor(Color::WHITE)
+                .border(Color::WHITE),
+            ..default()
+        };
+        let widget = app
+            .world_mut()
+            .spawn((buffer, BorderColor::all(red), style))
+            .id();
+        app.update();
+
+        app.world_mut()
+            .resource_mut::<FocusedWidget>()
+            .focus(widget);
+        app.update();
+        app.world_mut().resource_mut::<CosmicTheme>().variant = ThemeVariant::Dark;
+        app.update();
+        app.world_mut().resource_mut::<FocusedWidget>().unfocus();
+        app.update();
+
+        let dark = ThemeDefaults::dark();
+        let widget = app.world().entity(widget);
+        assert_eq!(
+            widget.get::<CosmicBackgroundColor>().unwrap().0,
+            dark.background
+        );
+        assert_eq!(widget.get::<CursorColor>().unwrap().0, dark.cursor);
+        assert_eq!(widget.get::<BorderColor>().unwrap().top, red);
+    }
+}
@@ -29,6 +29,7 @@ impl<T: Into<Color>> ColorExtras for T {
 }
 
 /// System to unfocus editors when \[Esc\] is pressed
+#[deprecated(note = "Escape unfocuses by default now, see `FocusPolicy`")]
 pub fn deselect_editor_on_esc(i: Res<ButtonInput<KeyCode>>, mut focus: ResMut<FocusedWidget>) {
     if i.just_pressed(KeyCode::Escape) {
         focus.0 = None;